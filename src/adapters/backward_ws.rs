@@ -1 +1,175 @@
+use std::{cell::RefCell, collections::HashMap, io, net::ToSocketAddrs, rc::Rc};
 
+use ntex::{
+    http::{StatusCode, body::BodySize, h1, header::AUTHORIZATION},
+    io::{DispatchItem, Dispatcher, DispatcherConfig},
+    rt,
+    service::fn_service,
+    time::Seconds,
+    util::Ready,
+    web::{self, App, HttpRequest, HttpResponse, types::State},
+    ws::{self, Frame, error::HandshakeError, error::WsError},
+};
+
+use crate::PipeOps;
+
+use super::{
+    auth::BackwardMethod,
+    ws::{DEFAULT_MAX_FRAME_SIZE, FrameSink, WsConn, WsRole},
+};
+
+/// 反向 WebSocket，由实现连接到我们
+#[derive(Debug, Clone)]
+pub struct BackwardWs {
+    pub auth: BackwardMethod,
+    /// 单帧上限，默认为 [`DEFAULT_MAX_FRAME_SIZE`]
+    pub max_frame_size: usize,
+}
+
+struct Registry {
+    auth: BackwardMethod,
+    max_frame_size: usize,
+    bots: RefCell<HashMap<i64, WsConn>>,
+    on_bot: Box<dyn Fn(WsConn)>,
+}
+
+impl Registry {
+    /// 同一账号重连时复用原来的 [`WsConn`]
+    fn bot(&self, self_id: i64) -> WsConn {
+        if let Some(conn) = self.bots.borrow().get(&self_id) {
+            return conn.clone();
+        }
+        let conn = WsConn::backward(self_id);
+        self.bots.borrow_mut().insert(self_id, conn.clone());
+        (self.on_bot)(conn.clone());
+        conn
+    }
+
+    fn check_auth(&self, req: &HttpRequest) -> Result<(), HttpResponse> {
+        let BackwardMethod::Header(token) = &self.auth else {
+            return Ok(());
+        };
+        let Some(value) = req.headers().get(AUTHORIZATION) else {
+            return Err(HttpResponse::Unauthorized().finish());
+        };
+        match value.to_str().ok().and_then(|v| {
+            v.strip_prefix("Bearer ")
+                .or_else(|| v.strip_prefix("Token "))
+        }) {
+            Some(t) if t == token => Ok(()),
+            _ => Err(HttpResponse::Forbidden().finish()),
+        }
+    }
+}
+
+fn header<'a>(req: &'a HttpRequest, name: &str) -> Option<&'a str> {
+    req.headers().get(name)?.to_str().ok()
+}
+
+async fn accept(
+    req: HttpRequest,
+    registry: State<Rc<Registry>>,
+) -> Result<HttpResponse, web::Error> {
+    if let Err(res) = registry.check_auth(&req) {
+        return Ok(res);
+    }
+    let Some(self_id) = header(&req, "X-Self-ID").and_then(|id| id.parse::<i64>().ok()) else {
+        return Ok(HttpResponse::BadRequest().finish());
+    };
+    let Some(role) = header(&req, "X-Client-Role").and_then(WsRole::from_header) else {
+        return Ok(HttpResponse::BadRequest().finish());
+    };
+    let conn = registry.bot(self_id);
+
+    // 与 `ws::start` 相同，只是换成可以设置帧上限的 codec
+    let res = ws::handshake(req.head())?.finish().into_parts().0;
+    let (io, codec) = req
+        .head()
+        .take_io()
+        .ok_or(HandshakeError::NoWebsocketUpgrade)?;
+    io.encode(h1::Message::Item((res, BodySize::Empty)), &codec)
+        .map_err(|_| HandshakeError::NoWebsocketUpgrade)?;
+
+    let codec = ws::Codec::new().max_size(registry.max_frame_size);
+    let sink = FrameSink::new(io.get_ref(), codec.clone());
+    let handler = conn.attach(role, sink.clone(), registry.max_frame_size);
+    let on_disconnect = sink.io().on_disconnect();
+    let detach = handler.clone();
+    rt::spawn(async move {
+        on_disconnect.await;
+        tracing::info!(self_id, role = role.to_header(), "backward ws closed");
+        conn.detach(&detach);
+    });
+
+    let service = fn_service(move |item: DispatchItem<ws::Codec>| {
+        Ready::from(match item {
+            DispatchItem::Item(frame) => {
+                if matches!(frame, Frame::Close(_)) {
+                    let sink = sink.clone();
+                    rt::spawn(async move { sink.io().close() });
+                }
+                Ok(handler.handle(frame))
+            }
+            DispatchItem::WBackPressureEnabled | DispatchItem::WBackPressureDisabled => Ok(None),
+            DispatchItem::KeepAliveTimeout => Err(WsError::<()>::KeepAlive),
+            DispatchItem::ReadTimeout => Err(WsError::ReadTimeout),
+            DispatchItem::DecoderError(e) | DispatchItem::EncoderError(e) => {
+                Err(WsError::Protocol(e))
+            }
+            DispatchItem::Disconnect(e) => Err(WsError::Disconnected(e)),
+        })
+    });
+    let cfg = DispatcherConfig::default();
+    cfg.set_keepalive_timeout(Seconds::ZERO);
+    rt::spawn(async move {
+        if let Err(e) = Dispatcher::new(io, codec, service, &cfg).await {
+            tracing::warn!(error = ?e, self_id, "backward ws dispatcher failed");
+        }
+    });
+
+    Ok(HttpResponse::new(StatusCode::OK))
+}
+
+impl BackwardWs {
+    pub fn new(auth: BackwardMethod) -> Self {
+        Self {
+            auth,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+        }
+    }
+
+    pub fn with_max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    /// 监听 `addr`，每个新账号第一次连上时调用一次 `on_bot`
+    ///
+    /// 重连沿用原来的 [`WsConn`]，`on_bot` 中的 [`WsConn::recv`] 循环会在重连后继续收到事件
+    ///
+    /// [`WsConn`] 只能在创建它的线程使用，所以只启动一个 worker
+    pub async fn run<A, F, Fut>(self, addr: A, on_bot: F) -> io::Result<()>
+    where
+        A: ToSocketAddrs,
+        F: Fn(WsConn) -> Fut + Send + Clone + 'static,
+        Fut: Future<Output = ()> + 'static,
+    {
+        web::HttpServer::new(move || {
+            let on_bot = on_bot.clone();
+            Rc::new(Registry {
+                auth: self.auth.clone(),
+                max_frame_size: self.max_frame_size,
+                bots: RefCell::default(),
+                on_bot: Box::new(move |conn| {
+                    rt::spawn(on_bot(conn));
+                }),
+            })
+            .pipe(|registry| App::new().state(registry))
+            .default_service(web::route().to(accept))
+        })
+        .workers(1)
+        .bind(addr)?
+        .run()
+        .await
+    }
+}
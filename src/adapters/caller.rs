@@ -0,0 +1,196 @@
+use std::pin::Pin;
+
+use ntex::{
+    http::{Client, Uri},
+    time::Millis,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    auth::ForwardMethod,
+    http::{ActionResult, JsonReq, Mode, RawAction, Response},
+    ws::WsConn,
+};
+use crate::{
+    api::{ForwardNode, GetForwardMessageRequest},
+    error::{Error, Transport},
+    models::message::Forward,
+};
+
+/// 与传输方式无关的 API 调用
+pub trait ApiCaller {
+    fn call<T, E>(
+        &self,
+        data: T,
+        mode: Mode,
+    ) -> impl Future<Output = Result<Response<T::Output, E>, Error>>
+    where
+        T: JsonReq,
+        E: for<'de> Deserialize<'de>;
+
+    /// 按名称调用任意动作
+    fn call_raw<P: Serialize>(
+        &self,
+        data: RawAction<P>,
+        mode: Mode,
+    ) -> impl Future<Output = Result<Response<Value, Value>, Error>>;
+
+    /// 调用使用的传输方式，记录在 [`Error::transport`] 中，包装其他调用方时返回内层的
+    fn transport(&self) -> Option<Transport> {
+        None
+    }
+
+    /// 调用并按 `retcode` 区分结果，实现返回的失败也作为 [`Error`]
+    fn invoke<T>(
+        &self,
+        data: T,
+        mode: Mode,
+    ) -> impl Future<Output = Result<ActionResult<T::Output>, Error>>
+    where
+        T: JsonReq,
+    {
+        async move {
+            let response = self.call::<T, Value>(data, mode).await?;
+            response
+                .into_result()
+                .map_err(|e| Error::from(e).or_transport(self.transport()))
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpCaller {
+    /// ntex 默认 5 秒请求超时、10 秒读取超时，超时为 [`ErrorKind::Timeout`](crate::error::ErrorKind::Timeout)；
+    /// 使用更长的 [`TimeoutPolicy`](super::timeout::TimeoutPolicy) 时需要关闭，见 [`HttpCaller::without_client_timeout`]
+    pub client: Client,
+    pub base: Uri,
+    pub auth: ForwardMethod,
+}
+
+impl HttpCaller {
+    /// 保留 ntex 客户端自带的超时，不包装 [`Timeout`](super::timeout::Timeout) 也不会一直等待
+    pub fn new(base: Uri, auth: ForwardMethod) -> Self {
+        Self {
+            client: Client::new(),
+            base,
+            auth,
+        }
+    }
+
+    /// 关闭 ntex 客户端自带的超时，时限完全交给 [`Timeout`](super::timeout::Timeout) 控制
+    ///
+    /// 需要用设置了时限的 [`TimeoutPolicy`](super::timeout::TimeoutPolicy) 包装，否则请求可能一直等待
+    pub fn without_client_timeout(base: Uri, auth: ForwardMethod) -> Self {
+        let client = Client::build()
+            .disable_timeout()
+            .response_payload_timeout(Millis::ZERO)
+            .finish();
+        Self { client, base, auth }
+    }
+}
+
+impl ApiCaller for HttpCaller {
+    fn transport(&self) -> Option<Transport> {
+        Some(Transport::Http)
+    }
+
+    async fn call<T, E>(&self, data: T, mode: Mode) -> Result<Response<T::Output, E>, Error>
+    where
+        T: JsonReq,
+        E: for<'de> Deserialize<'de>,
+    {
+        data.send_json(self.client.clone(), &self.base, mode, self.auth.clone())
+            .await
+            .map_err(|e| Error::from(e).with_action(T::ENTRY))
+    }
+
+    async fn call_raw<P: Serialize>(
+        &self,
+        data: RawAction<P>,
+        mode: Mode,
+    ) -> Result<Response<Value, Value>, Error> {
+        let action = data.action.clone();
+        data.send_json(self.client.clone(), &self.base, mode, self.auth.clone())
+            .await
+            .map_err(|e| Error::from(e).with_action(action))
+    }
+}
+
+/// 正向与反向 WebSocket 都得到 [`WsConn`]
+impl ApiCaller for WsConn {
+    fn transport(&self) -> Option<Transport> {
+        Some(Transport::WebSocket)
+    }
+
+    async fn call<T, E>(&self, data: T, mode: Mode) -> Result<Response<T::Output, E>, Error>
+    where
+        T: JsonReq,
+        E: for<'de> Deserialize<'de>,
+    {
+        WsConn::call(self, data, mode)
+            .await
+            .map_err(|e| Error::from(e).with_action(T::ENTRY))
+    }
+
+    async fn call_raw<P: Serialize>(
+        &self,
+        data: RawAction<P>,
+        mode: Mode,
+    ) -> Result<Response<Value, Value>, Error> {
+        let action = data.action.clone();
+        WsConn::call_raw(self, data, mode)
+            .await
+            .map_err(|e| Error::from(e).with_action(action))
+    }
+}
+
+/// 展开后的合并转发
+#[derive(Debug, Clone)]
+pub struct ForwardTree {
+    pub id: String,
+    /// 超过深度限制时为 `None`
+    pub nodes: Option<Vec<ForwardTreeNode>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ForwardTreeNode {
+    pub node: ForwardNode,
+    /// `content` 中嵌套的合并转发，按出现顺序
+    pub children: Vec<ForwardTree>,
+}
+
+/// 递归调用 `get_forward_msg` 展开合并转发，最多展开 `max_depth` 层
+pub async fn expand_forward<C: ApiCaller>(
+    caller: &C,
+    forward: &Forward,
+    max_depth: usize,
+) -> Result<ForwardTree, Error> {
+    expand(caller, forward.id.clone(), max_depth).await
+}
+
+type Expand<'a> = Pin<Box<dyn Future<Output = Result<ForwardTree, Error>> + 'a>>;
+
+fn expand<C: ApiCaller>(caller: &C, id: String, depth: usize) -> Expand<'_> {
+    Box::pin(async move {
+        if depth == 0 {
+            return Ok(ForwardTree { id, nodes: None });
+        }
+        let data = caller
+            .invoke(GetForwardMessageRequest { id: id.clone() }, Mode::Default)
+            .await?
+            .into_ok()?;
+        let mut nodes = Vec::with_capacity(data.messages.len());
+        for node in data.messages {
+            let mut children = Vec::new();
+            for forward in node.forwards() {
+                children.push(expand(caller, forward.id.clone(), depth - 1).await?);
+            }
+            nodes.push(ForwardTreeNode { node, children });
+        }
+        Ok(ForwardTree {
+            id,
+            nodes: Some(nodes),
+        })
+    })
+}
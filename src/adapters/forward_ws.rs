@@ -1 +1,141 @@
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    str::FromStr,
+};
 
+use ntex::{
+    http::{
+        Uri,
+        uri::{InvalidUri, InvalidUriParts, PathAndQuery},
+    },
+    rt,
+    service::fn_service,
+    util::Ready,
+    ws::{
+        WsClient,
+        error::{WsClientBuilderError, WsClientError},
+    },
+};
+
+use crate::{PipeOps, utils::WithPathAndQuery};
+
+use super::{
+    auth::ForwardMethod,
+    ws::{DEFAULT_MAX_FRAME_SIZE, FrameSink, WsConn, WsRole},
+};
+
+/// 正向 WebSocket，由我们连接到实现
+#[derive(Debug, Clone)]
+pub struct ForwardWs {
+    pub base: Uri,
+    pub auth: ForwardMethod,
+    /// 单帧上限，默认为 [`DEFAULT_MAX_FRAME_SIZE`]
+    pub max_frame_size: usize,
+}
+
+#[derive(Debug)]
+pub enum ForwardWsError {
+    EncodePathAndQuery(InvalidUri),
+    EncodeUrl(InvalidUriParts),
+    Build(WsClientBuilderError),
+    Connect(WsClientError),
+}
+
+impl Display for ForwardWsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ForwardWsError::EncodePathAndQuery(_) => "invalid websocket path",
+            ForwardWsError::EncodeUrl(_) => "invalid websocket url",
+            ForwardWsError::Build(_) => "failed to build websocket client",
+            ForwardWsError::Connect(_) => "failed to connect websocket",
+        })
+    }
+}
+
+impl Error for ForwardWsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ForwardWsError::EncodePathAndQuery(e) => Some(e),
+            ForwardWsError::EncodeUrl(e) => Some(e),
+            ForwardWsError::Build(e) => Some(e),
+            ForwardWsError::Connect(e) => Some(e),
+        }
+    }
+}
+
+impl ForwardWs {
+    pub fn new(base: Uri, auth: ForwardMethod) -> Self {
+        Self {
+            base,
+            auth,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+        }
+    }
+
+    pub fn with_max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    fn url(&self, role: WsRole) -> Result<Uri, ForwardWsError> {
+        match &self.auth {
+            ForwardMethod::Query(t) => "?access_token=".to_owned() + t,
+            _ => "".into(),
+        }
+        .pipe(|auth| format!("{}{}", role.to_path(), auth))
+        .pipe(|x| PathAndQuery::from_str(&x))
+        .map_err(ForwardWsError::EncodePathAndQuery)?
+        .pipe(|path| self.base.clone().with_path_and_query(path))
+        .map_err(ForwardWsError::EncodeUrl)?
+        .0
+        .pipe(Ok)
+    }
+
+    async fn attach(&self, conn: &WsConn, role: WsRole) -> Result<(), ForwardWsError> {
+        let mut builder = WsClient::build(self.url(role)?);
+        builder.max_frame_size(self.max_frame_size);
+        if let ForwardMethod::Header(token) = &self.auth {
+            builder.bearer_auth(token);
+        }
+        let ws = builder
+            .finish()
+            .map_err(ForwardWsError::Build)?
+            .connect()
+            .await
+            .map_err(ForwardWsError::Connect)?;
+
+        let sink = FrameSink::new(ws.sink().io().clone(), ws.codec().clone());
+        let handler = conn.attach(role, sink, self.max_frame_size);
+        let conn = conn.clone();
+        rt::spawn(async move {
+            let frames = handler.clone();
+            let result = ws
+                .seal()
+                .start(fn_service(move |frame| {
+                    Ready::Ok::<_, ()>(frames.handle(frame))
+                }))
+                .await;
+            if let Err(e) = result {
+                tracing::warn!(error = ?e, role = role.to_header(), "forward ws closed");
+            }
+            conn.detach(&handler);
+        });
+        Ok(())
+    }
+
+    /// 连接单个端点，`/` 同时收发，`/api` 只发 API，`/event` 只收事件
+    pub async fn connect(&self, role: WsRole) -> Result<WsConn, ForwardWsError> {
+        let conn = WsConn::forward();
+        self.attach(&conn, role).await?;
+        Ok(conn)
+    }
+
+    /// 分别连接 `/api` 和 `/event`，合并为一个 [`WsConn`]
+    pub async fn connect_split(&self) -> Result<WsConn, ForwardWsError> {
+        let conn = WsConn::forward();
+        self.attach(&conn, WsRole::Api).await?;
+        self.attach(&conn, WsRole::Event).await?;
+        Ok(conn)
+    }
+}
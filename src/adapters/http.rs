@@ -1,14 +1,21 @@
-use std::str::FromStr;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display},
+    str::FromStr,
+};
 
 use ntex::http::{
-    Client, Uri,
+    Client, StatusCode, Uri,
     client::{
         ClientRequest, SendClientRequest,
         error::{JsonPayloadError, SendRequestError},
     },
     uri::{InvalidUri, InvalidUriParts, PathAndQuery},
 };
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, de, de::DeserializeOwned};
+use serde_json::Value;
 
 use crate::{PipeOps, utils::WithPathAndQuery};
 
@@ -25,14 +32,79 @@ pub struct HttpExecUnit<S: Serialize> {
     pub data: S,
 }
 
+#[derive(Debug)]
 pub enum ExecError {
     EncodePathAndQuery(InvalidUri),
     EncodeUrl(InvalidUriParts),
     EncodeQueryData(serde_urlencoded::ser::Error),
-    Send(SendRequestError),
+    Send(SendError),
     Decode(JsonPayloadError),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendErrorKind {
+    /// 连接失败，请求没有发出
+    Connect,
+    /// HTTP 客户端自身的请求超时
+    Timeout,
+    Other,
+}
+
+/// 发送请求失败
+///
+/// ntex 的 [`SendRequestError`] 可能带有 `Box<dyn Error>`，不是 `Send`，这里只保留分类与信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SendError {
+    pub kind: SendErrorKind,
+    pub message: String,
+}
+
+impl From<SendRequestError> for SendError {
+    fn from(e: SendRequestError) -> Self {
+        let kind = match e {
+            SendRequestError::Connect(_) => SendErrorKind::Connect,
+            SendRequestError::Timeout => SendErrorKind::Timeout,
+            _ => SendErrorKind::Other,
+        };
+        Self {
+            kind,
+            message: e.to_string(),
+        }
+    }
+}
+
+impl Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for SendError {}
+
+impl Display for ExecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ExecError::EncodePathAndQuery(_) => "invalid action path",
+            ExecError::EncodeUrl(_) => "invalid action url",
+            ExecError::EncodeQueryData(_) => "failed to encode query",
+            ExecError::Send(_) => "failed to send request",
+            ExecError::Decode(_) => "failed to decode response",
+        })
+    }
+}
+
+impl Error for ExecError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ExecError::EncodePathAndQuery(e) => Some(e),
+            ExecError::EncodeUrl(e) => Some(e),
+            ExecError::EncodeQueryData(e) => Some(e),
+            ExecError::Send(e) => Some(e),
+            ExecError::Decode(e) => Some(e),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Method {
     Urlencoded,
@@ -72,16 +144,265 @@ impl<T, E> From<Result<T, E>> for CustomResult<T, E> {
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+/// 动作的原始返回，用 [`Response::into_result`] 按 `retcode` 区分结果
+///
+/// 成功时 `data` 必须能解析为 `O`，缺少时按 `null` 解析，不匹配时整个返回解析失败并保留 serde 的错误；
+/// 失败时依次尝试 `E`、`O`，都不匹配时丢弃
+#[derive(Debug, Clone)]
 pub struct Response<O, E> {
     pub status: String,
     pub retcode: i32,
     pub data: Option<CustomResult<O, E>>,
+    /// go-cqhttp 为 `msg`
+    pub message: Option<String>,
+    pub wording: Option<String>,
+    /// 调用的 [`Entry::ENTRY`]，由发送方填入
+    pub action: Cow<'static, str>,
+    /// 调用时的 [`Mode`]，由发送方填入
+    pub mode: Mode,
+}
+
+impl<'de, O, E> Deserialize<'de> for Response<O, E>
+where
+    O: DeserializeOwned,
+    E: DeserializeOwned,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Raw {
+            status: String,
+            retcode: i32,
+            #[serde(default)]
+            data: Value,
+            #[serde(default, alias = "msg")]
+            message: Option<String>,
+            #[serde(default)]
+            wording: Option<String>,
+        }
+
+        let raw = Raw::deserialize(deserializer)?;
+        let data = if raw.retcode == 0 && raw.status != "failed" {
+            O::deserialize(raw.data)
+                .map_err(de::Error::custom)?
+                .pipe(CustomResult::Ok)
+                .pipe(Some)
+        } else if raw.data.is_null() {
+            None
+        } else {
+            E::deserialize(&raw.data)
+                .map(CustomResult::Err)
+                .or_else(|_| O::deserialize(&raw.data).map(CustomResult::Ok))
+                .ok()
+        };
+        Ok(Response {
+            status: raw.status,
+            retcode: raw.retcode,
+            data,
+            message: raw.message,
+            wording: raw.wording,
+            action: Cow::Borrowed(""),
+            mode: Mode::Default,
+        })
+    }
+}
+
+/// 动作已被实现接受，稍后执行，没有返回数据
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Accepted {
+    pub action: Cow<'static, str>,
+    pub mode: Mode,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActionResult<O> {
+    Ok(O),
+    /// `retcode` 为 1，通常是以 [`Mode::Async`] 或 [`Mode::RateLimited`] 调用
+    Accepted(Accepted),
+}
+
+impl<O> ActionResult<O> {
+    pub fn ok(self) -> Option<O> {
+        match self {
+            ActionResult::Ok(value) => Some(value),
+            ActionResult::Accepted(_) => None,
+        }
+    }
+
+    /// 需要返回数据时使用，`?` 可以把 [`Accepted`] 转为 [`crate::Error`]
+    pub fn into_ok(self) -> Result<O, Accepted> {
+        match self {
+            ActionResult::Ok(value) => Ok(value),
+            ActionResult::Accepted(accepted) => Err(accepted),
+        }
+    }
+
+    pub fn is_accepted(&self) -> bool {
+        matches!(self, ActionResult::Accepted(_))
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(O) -> U) -> ActionResult<U> {
+        match self {
+            ActionResult::Ok(value) => ActionResult::Ok(f(value)),
+            ActionResult::Accepted(accepted) => ActionResult::Accepted(accepted),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Retcode {
+    /// 100，参数缺失或错误
+    BadParams,
+    /// 102，没有结果或数据无效
+    NoResult,
+    /// 103，操作失败
+    OperationFailed,
+    /// 104，凭证失效
+    InvalidCredentials,
+    /// 201，工作线程异常
+    WorkerException,
+    /// 1400，请求格式错误
+    BadRequest,
+    /// 1401，缺少 access token
+    Unauthorized,
+    /// 1403，access token 不符
+    Forbidden,
+    /// 1404，动作不存在
+    NotFound,
+    /// 实现自定义的错误码
+    Other(i32),
+}
+
+impl From<i32> for Retcode {
+    fn from(code: i32) -> Self {
+        match code {
+            100 => Retcode::BadParams,
+            102 => Retcode::NoResult,
+            103 => Retcode::OperationFailed,
+            104 => Retcode::InvalidCredentials,
+            201 => Retcode::WorkerException,
+            1400 => Retcode::BadRequest,
+            1401 => Retcode::Unauthorized,
+            1403 => Retcode::Forbidden,
+            1404 => Retcode::NotFound,
+            code => Retcode::Other(code),
+        }
+    }
+}
+
+impl Retcode {
+    pub fn code(self) -> i32 {
+        match self {
+            Retcode::BadParams => 100,
+            Retcode::NoResult => 102,
+            Retcode::OperationFailed => 103,
+            Retcode::InvalidCredentials => 104,
+            Retcode::WorkerException => 201,
+            Retcode::BadRequest => 1400,
+            Retcode::Unauthorized => 1401,
+            Retcode::Forbidden => 1403,
+            Retcode::NotFound => 1404,
+            Retcode::Other(code) => code,
+        }
+    }
+}
+
+/// 实现返回的失败
+#[derive(Debug, Clone)]
+pub struct ActionError<E> {
+    pub action: Cow<'static, str>,
+    pub retcode: Retcode,
+    pub message: Option<String>,
+    pub wording: Option<String>,
+    /// 失败时附带的数据
+    pub data: Option<E>,
+}
+
+impl<E> Display for ActionError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` returned retcode {}",
+            self.action,
+            self.retcode.code()
+        )?;
+        match (&self.wording, &self.message) {
+            (Some(wording), _) => write!(f, ": {wording}"),
+            (None, Some(message)) => write!(f, ": {message}"),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+impl<E: fmt::Debug> Error for ActionError<E> {}
+
+impl<O, E> Response<O, E> {
+    /// `retcode` 为 0 时成功，为 1 时已异步执行，其余都是失败
+    ///
+    /// 解析得到的成功返回总带有数据；手动构造而没有数据时按 `null` 解析 `O`，解析不了视为 [`Retcode::NoResult`]
+    pub fn into_result(self) -> Result<ActionResult<O>, ActionError<E>>
+    where
+        O: for<'de> Deserialize<'de>,
+    {
+        let retcode = match self.retcode {
+            0 if self.status != "failed" => match self.data {
+                Some(CustomResult::Ok(data)) => return Ok(ActionResult::Ok(data)),
+                _ => match O::deserialize(Value::Null) {
+                    Ok(data) => return Ok(ActionResult::Ok(data)),
+                    Err(_) => Retcode::NoResult,
+                },
+            },
+            1 => {
+                return Ok(ActionResult::Accepted(Accepted {
+                    action: self.action,
+                    mode: self.mode,
+                }));
+            }
+            code => Retcode::from(code),
+        };
+        Err(ActionError {
+            action: self.action,
+            retcode,
+            message: self.message.filter(|message| !message.is_empty()),
+            wording: self.wording.filter(|wording| !wording.is_empty()),
+            data: match self.data {
+                Some(CustomResult::Err(data)) => Some(data),
+                _ => None,
+            },
+        })
+    }
+}
+
+/// 鉴权失败与动作不存在时只有状态码，没有 JSON，对应 `retcode` 为 1400 起的同名错误
+///
+/// 其他状态码交给 JSON 解析，原样退回 `action`
+fn status_response<O, E>(
+    status: StatusCode,
+    action: Cow<'static, str>,
+) -> Result<Response<O, E>, Cow<'static, str>> {
+    if !matches!(
+        status,
+        StatusCode::BAD_REQUEST
+            | StatusCode::UNAUTHORIZED
+            | StatusCode::FORBIDDEN
+            | StatusCode::NOT_FOUND
+    ) {
+        return Err(action);
+    }
+    Ok(Response {
+        status: "failed".into(),
+        retcode: 1000 + i32::from(status.as_u16()),
+        data: None,
+        message: status.canonical_reason().map(Into::into),
+        wording: None,
+        action,
+        mode: Mode::Default,
+    })
 }
 
 impl<D: Serialize> HttpExecUnit<D> {
     async fn raw_exec<O, E>(
         self,
+        action: Cow<'static, str>,
         method: impl Fn(Client, Uri) -> ClientRequest,
         sender: impl Fn(ClientRequest, D) -> SendClientRequest,
     ) -> Result<Response<O, E>, ExecError>
@@ -89,15 +410,22 @@ impl<D: Serialize> HttpExecUnit<D> {
         O: for<'de> Deserialize<'de>,
         E: for<'de> Deserialize<'de>,
     {
-        self.client
+        let mut res = self
+            .client
             .pipe(|client| method(client, self.url.0))
             .pipe(|client| add_bearer_header(&self.auth, client))
             .pipe(|req| sender(req, self.data))
             .await
-            .map_err(ExecError::Send)?
-            .json::<Response<O, E>>()
+            .map_err(|e| ExecError::Send(e.into()))?;
+
+        let action = match status_response(res.status(), action) {
+            Ok(response) => return Ok(response),
+            Err(action) => action,
+        };
+        res.json::<Response<O, E>>()
             .await
             .map_err(ExecError::Decode)
+            .map(|response| Response { action, ..response })
     }
 
     pub async fn exec_query<O, E>(self) -> Result<Response<O, E>, ExecError>
@@ -106,8 +434,12 @@ impl<D: Serialize> HttpExecUnit<D> {
         O: for<'de> Deserialize<'de>,
         E: for<'de> Deserialize<'de>,
     {
-        self.raw_exec(|client, uri| client.get(uri), |req, _| req.send())
-            .await
+        self.raw_exec(
+            D::ENTRY.into(),
+            |client, uri| client.get(uri),
+            |req, _| req.send(),
+        )
+        .await
     }
 
     pub async fn exec_form<O, E>(self) -> Result<Response<O, E>, ExecError>
@@ -117,6 +449,7 @@ impl<D: Serialize> HttpExecUnit<D> {
         E: for<'de> Deserialize<'de>,
     {
         self.raw_exec(
+            D::ENTRY.into(),
             |client, uri| client.post(uri),
             |req, data| req.send_form(&data),
         )
@@ -130,6 +463,24 @@ impl<D: Serialize> HttpExecUnit<D> {
         E: for<'de> Deserialize<'de>,
     {
         self.raw_exec(
+            D::ENTRY.into(),
+            |client, uri| client.post(uri),
+            |req, data| req.send_json(&data),
+        )
+        .await
+    }
+
+    /// 以 JSON 调用名为 `action` 的动作，不要求 `D` 实现 [`Entry`]
+    pub async fn exec_action<O, E>(
+        self,
+        action: impl Into<Cow<'static, str>>,
+    ) -> Result<Response<O, E>, ExecError>
+    where
+        O: for<'de> Deserialize<'de>,
+        E: for<'de> Deserialize<'de>,
+    {
+        self.raw_exec(
+            action.into(),
             |client, uri| client.post(uri),
             |req, data| req.send_json(&data),
         )
@@ -137,8 +488,9 @@ impl<D: Serialize> HttpExecUnit<D> {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
 pub enum Mode {
+    #[default]
     Default,
     Async,
     RateLimited,
@@ -154,6 +506,67 @@ impl Mode {
     }
 }
 
+/// 按名称前缀划分的动作类别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ActionClass {
+    /// `get_*`、`can_*`
+    Query,
+    /// `send_*`
+    Send,
+    /// `set_*`、`delete_*`
+    Manage,
+    Other,
+}
+
+impl ActionClass {
+    pub fn of(action: &str) -> Self {
+        if action.starts_with("get_") || action.starts_with("can_") {
+            ActionClass::Query
+        } else if action.starts_with("send_") {
+            ActionClass::Send
+        } else if action.starts_with("set_") || action.starts_with("delete_") {
+            ActionClass::Manage
+        } else {
+            ActionClass::Other
+        }
+    }
+}
+
+/// 为每个动作选择 [`Mode`]，按动作名、类别、默认值的顺序查找
+#[derive(Debug, Clone, Default)]
+pub struct ModePolicy {
+    pub default: Mode,
+    pub classes: HashMap<ActionClass, Mode>,
+    pub actions: HashMap<Cow<'static, str>, Mode>,
+}
+
+impl ModePolicy {
+    pub fn new(default: Mode) -> Self {
+        Self {
+            default,
+            ..Self::default()
+        }
+    }
+
+    pub fn class(mut self, class: ActionClass, mode: Mode) -> Self {
+        self.classes.insert(class, mode);
+        self
+    }
+
+    pub fn action(mut self, action: impl Into<Cow<'static, str>>, mode: Mode) -> Self {
+        self.actions.insert(action.into(), mode);
+        self
+    }
+
+    pub fn mode_for(&self, action: &str) -> Mode {
+        self.actions
+            .get(action)
+            .or_else(|| self.classes.get(&ActionClass::of(action)))
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
 pub trait Entry: Serialize {
     type Output: for<'de> Deserialize<'de>;
     const ENTRY: &'static str;
@@ -164,7 +577,7 @@ trait ReplacePath: WithPathAndQuery {
     where
         Self: Sized,
     {
-        PathAndQuery::from_str(&path)
+        PathAndQuery::from_str(path)
             .map_err(ExecError::EncodePathAndQuery)?
             .pipe(|path| self.with_path_and_query(path))
             .map_err(ExecError::EncodeUrl)
@@ -173,6 +586,57 @@ trait ReplacePath: WithPathAndQuery {
 
 impl ReplacePath for Uri {}
 
+/// `/<action><suffix>`，`Query` 鉴权时带上 `access_token`
+fn action_url(
+    base: &Uri,
+    action: &str,
+    mode: Mode,
+    auth: &ForwardMethod,
+) -> Result<HttpEntry, ExecError> {
+    match auth {
+        ForwardMethod::Query(t) => "?access_token=".to_owned() + t,
+        _ => "".into(),
+    }
+    .pipe(|auth| format!("/{}{}{}", action, mode.to_suffix(), auth))
+    .pipe(|x| base.clone().replace_path(&x))
+}
+
+/// 按名称调用任意动作，用于 [`crate::api`] 中没有的动作
+///
+/// `params` 可以是 [`Value`]，也可以是任意可序列化的结构或 map
+#[derive(Debug, Clone)]
+pub struct RawAction<P = Value> {
+    pub action: Cow<'static, str>,
+    pub params: P,
+}
+
+impl<P: Serialize> RawAction<P> {
+    pub fn new(action: impl Into<Cow<'static, str>>, params: P) -> Self {
+        Self {
+            action: action.into(),
+            params,
+        }
+    }
+
+    pub async fn send_json(
+        self,
+        client: Client,
+        base: &Uri,
+        mode: Mode,
+        auth: ForwardMethod,
+    ) -> Result<Response<Value, Value>, ExecError> {
+        HttpExecUnit {
+            client,
+            url: action_url(base, &self.action, mode, &auth)?,
+            auth,
+            data: self.params,
+        }
+        .exec_action(self.action)
+        .await
+        .map(|response| Response { mode, ..response })
+    }
+}
+
 pub trait CqReq: Entry {
     fn send_query<E>(
         self,
@@ -201,6 +665,7 @@ pub trait CqReq: Entry {
             })
             .exec_query()
             .await
+            .map(|response| Response { mode, ..response })
         }
     }
     fn send_form<E>(
@@ -215,20 +680,16 @@ pub trait CqReq: Entry {
         Self: Sized,
     {
         async move {
-            match &auth {
-                ForwardMethod::Query(t) => "?access_token=".to_owned() + t,
-                _ => "".into(),
-            }
-            .pipe(|auth| format!("/{}{}{}", Self::ENTRY, mode.to_suffix(), auth))
-            .pipe(|x| base.clone().replace_path(&x))?
-            .pipe(|url| HttpExecUnit {
-                client,
-                url,
-                auth,
-                data: self,
-            })
-            .exec_form()
-            .await
+            action_url(base, Self::ENTRY, mode, &auth)?
+                .pipe(|url| HttpExecUnit {
+                    client,
+                    url,
+                    auth,
+                    data: self,
+                })
+                .exec_form()
+                .await
+                .map(|response| Response { mode, ..response })
         }
     }
 }
@@ -246,20 +707,126 @@ pub trait JsonReq: Entry {
         Self: Sized,
     {
         async move {
-            match &auth {
-                ForwardMethod::Query(t) => "?access_token=".to_owned() + t,
-                _ => "".into(),
-            }
-            .pipe(|auth| format!("/{}{}{}", Self::ENTRY, mode.to_suffix(), auth))
-            .pipe(|x| base.clone().replace_path(&x))?
-            .pipe(|url| HttpExecUnit {
-                client,
-                url,
-                auth,
-                data: self,
+            action_url(base, Self::ENTRY, mode, &auth)?
+                .pipe(|url| HttpExecUnit {
+                    client,
+                    url,
+                    auth,
+                    data: self,
+                })
+                .exec_json()
+                .await
+                .map(|response| Response { mode, ..response })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Sent {
+        message_id: i32,
+    }
+
+    fn parse(value: Value) -> Result<Response<Sent, Value>, serde_json::Error> {
+        serde_json::from_value(value)
+    }
+
+    #[test]
+    fn success_parses_data() {
+        let response = parse(json!({
+            "status": "ok",
+            "retcode": 0,
+            "data": { "message_id": 5 },
+        }))
+        .unwrap();
+        assert_eq!(
+            response.into_result().unwrap(),
+            ActionResult::Ok(Sent { message_id: 5 })
+        );
+    }
+
+    #[test]
+    fn mismatched_success_data_is_a_decode_error() {
+        let error = parse(json!({
+            "status": "ok",
+            "retcode": 0,
+            "data": { "message_id": "abc" },
+        }))
+        .unwrap_err();
+        assert!(error.to_string().contains("invalid type"), "{error}");
+    }
+
+    #[test]
+    fn failed_status_with_zero_retcode_is_an_error() {
+        let response = parse(json!({
+            "status": "failed",
+            "retcode": 0,
+            "data": { "reason": "blocked" },
+            "msg": "send failed",
+        }))
+        .unwrap();
+        let error = response.into_result().unwrap_err();
+        assert_eq!(error.retcode, Retcode::Other(0));
+        assert_eq!(error.message.as_deref(), Some("send failed"));
+        assert_eq!(error.data, Some(json!({ "reason": "blocked" })));
+    }
+
+    #[test]
+    fn accepted_without_data() {
+        let mut response = parse(json!({
+            "status": "async",
+            "retcode": 1,
+            "data": null,
+        }))
+        .unwrap();
+        response.action = "send_msg".into();
+        response.mode = Mode::Async;
+        assert_eq!(
+            response.into_result().unwrap(),
+            ActionResult::Accepted(Accepted {
+                action: "send_msg".into(),
+                mode: Mode::Async,
             })
-            .exec_json()
-            .await
+        );
+    }
+
+    #[test]
+    fn failure_keeps_message_and_wording() {
+        let response = parse(json!({
+            "status": "failed",
+            "retcode": 100,
+            "message": "",
+            "wording": "missing group_id",
+        }))
+        .unwrap();
+        let error = response.into_result().unwrap_err();
+        assert_eq!(error.retcode, Retcode::BadParams);
+        assert_eq!(error.message, None);
+        assert_eq!(error.wording.as_deref(), Some("missing group_id"));
+        assert!(error.data.is_none());
+    }
+
+    #[test]
+    fn client_errors_map_to_retcodes() {
+        for (status, retcode) in [
+            (StatusCode::BAD_REQUEST, Retcode::BadRequest),
+            (StatusCode::UNAUTHORIZED, Retcode::Unauthorized),
+            (StatusCode::FORBIDDEN, Retcode::Forbidden),
+            (StatusCode::NOT_FOUND, Retcode::NotFound),
+        ] {
+            let response = status_response::<Sent, Value>(status, "get_status".into()).unwrap();
+            let error = response.into_result().unwrap_err();
+            assert_eq!(error.retcode, retcode);
+            assert_eq!(error.action, "get_status");
+            assert_eq!(error.message.as_deref(), status.canonical_reason());
         }
+        assert!(
+            status_response::<Sent, Value>(StatusCode::INTERNAL_SERVER_ERROR, "".into()).is_err()
+        );
     }
 }
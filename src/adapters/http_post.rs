@@ -1 +1,128 @@
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    io,
+    marker::PhantomData,
+    net::ToSocketAddrs,
+    rc::Rc,
+};
 
+use hmac::{Hmac, Mac};
+use ntex::{
+    util::Bytes,
+    web::{self, App, HttpRequest, HttpResponse, types::State},
+};
+use serde::Deserialize;
+use sha1::Sha1;
+
+use crate::{PipeOps, models::quick_operation::QuickOperation};
+
+/// HTTP POST 上报，由实现把事件 POST 给我们
+#[derive(Debug, Clone)]
+pub struct HttpPost {
+    /// 配置后校验 `X-Signature`
+    pub secret: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureError {
+    Missing,
+    Malformed,
+    Mismatch,
+}
+
+impl Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SignatureError::Missing => "missing X-Signature",
+            SignatureError::Malformed => "malformed X-Signature",
+            SignatureError::Mismatch => "X-Signature mismatch",
+        })
+    }
+}
+
+impl Error for SignatureError {}
+
+/// 校验 `X-Signature: sha1=<hex>`，即以 `secret` 为密钥的 body 的 HMAC-SHA1
+pub fn verify_signature(
+    secret: &str,
+    body: &[u8],
+    signature: Option<&str>,
+) -> Result<(), SignatureError> {
+    let expected = signature
+        .ok_or(SignatureError::Missing)?
+        .strip_prefix("sha1=")
+        .ok_or(SignatureError::Malformed)?
+        .pipe(hex::decode)
+        .map_err(|_| SignatureError::Malformed)?;
+    Hmac::<Sha1>::new_from_slice(secret.as_bytes())
+        .expect("hmac accepts keys of any length")
+        .mut_pipe(|mac| mac.update(body))
+        .verify_slice(&expected)
+        .map_err(|_| SignatureError::Mismatch)
+}
+
+struct Receiver<E, F> {
+    secret: Option<String>,
+    handler: F,
+    _event: PhantomData<E>,
+}
+
+async fn receive<E, F, Fut>(
+    req: HttpRequest,
+    body: Bytes,
+    receiver: State<Rc<Receiver<E, F>>>,
+) -> HttpResponse
+where
+    E: for<'de> Deserialize<'de> + 'static,
+    F: Fn(E) -> Fut + 'static,
+    Fut: Future<Output = Option<QuickOperation>>,
+{
+    if let Some(secret) = &receiver.secret {
+        let signature = req
+            .headers()
+            .get("X-Signature")
+            .and_then(|v| v.to_str().ok());
+        if let Err(e) = verify_signature(secret, &body, signature) {
+            tracing::warn!(error = ?e, "rejecting http post event");
+            return HttpResponse::Forbidden().finish();
+        }
+    }
+    let event = match serde_json::from_slice::<E>(&body) {
+        Ok(event) => event,
+        Err(e) => {
+            tracing::warn!(error = %e, "invalid http post event");
+            return HttpResponse::BadRequest().finish();
+        }
+    };
+    match (receiver.handler)(event).await {
+        Some(operation) => HttpResponse::Ok().json(&operation),
+        None => HttpResponse::NoContent().finish(),
+    }
+}
+
+impl HttpPost {
+    /// 监听 `addr`，每收到一条事件调用一次 `handler`
+    ///
+    /// `handler` 返回的快速操作会作为响应体发回实现
+    pub async fn run<A, E, F, Fut>(self, addr: A, handler: F) -> io::Result<()>
+    where
+        A: ToSocketAddrs,
+        E: for<'de> Deserialize<'de> + 'static,
+        F: Fn(E) -> Fut + Send + Clone + 'static,
+        Fut: Future<Output = Option<QuickOperation>> + 'static,
+    {
+        web::HttpServer::new(move || {
+            Rc::new(Receiver {
+                secret: self.secret.clone(),
+                handler: handler.clone(),
+                _event: PhantomData::<E>,
+            })
+            .pipe(|receiver| App::new().state(receiver))
+            .default_service(web::route().to(receive::<E, F, Fut>))
+        })
+        .bind(addr)?
+        .run()
+        .await
+    }
+}
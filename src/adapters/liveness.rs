@@ -0,0 +1,140 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use ntex::{
+    channel::mpsc,
+    rt,
+    time::{Millis, sleep},
+};
+
+use crate::{
+    api::StatusResponse,
+    models::event::{Event, Heartbeat, Lifecycle, Meta, MetaEvent},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Liveness {
+    Online,
+    Offline,
+}
+
+#[derive(Debug, Clone)]
+pub struct BotLiveness {
+    pub liveness: Liveness,
+    pub last_seen: Instant,
+    /// 最近一次心跳声明的间隔
+    pub interval: Option<Duration>,
+    pub status: Option<StatusResponse>,
+    generation: u64,
+}
+
+/// 根据心跳间隔判断账号是否在线
+///
+/// 心跳停止超过 `tolerance` 个间隔，或心跳中 `status.online` 为 `false` 时视为离线
+#[derive(Debug, Clone)]
+pub struct LivenessTracker(Rc<LivenessInner>);
+
+#[derive(Debug)]
+struct LivenessInner {
+    tolerance: u32,
+    bots: RefCell<HashMap<i64, BotLiveness>>,
+    tx: mpsc::Sender<(i64, Liveness)>,
+    rx: mpsc::Receiver<(i64, Liveness)>,
+}
+
+impl Default for LivenessTracker {
+    fn default() -> Self {
+        Self::new(2)
+    }
+}
+
+impl LivenessTracker {
+    pub fn new(tolerance: u32) -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self(Rc::new(LivenessInner {
+            tolerance: tolerance.max(1),
+            bots: RefCell::default(),
+            tx,
+            rx,
+        }))
+    }
+
+    pub fn get(&self, self_id: i64) -> Option<BotLiveness> {
+        self.0.bots.borrow().get(&self_id).cloned()
+    }
+
+    pub fn is_online(&self, self_id: i64) -> bool {
+        self.get(self_id)
+            .is_some_and(|bot| bot.liveness == Liveness::Online)
+    }
+
+    /// 等待下一次在线状态变化
+    pub async fn recv(&self) -> Option<(i64, Liveness)> {
+        self.0.rx.recv().await
+    }
+
+    fn set(&self, self_id: i64, bot: &mut BotLiveness, liveness: Liveness) {
+        if bot.liveness != liveness {
+            bot.liveness = liveness;
+            let _ = self.0.tx.send((self_id, liveness));
+        }
+    }
+
+    /// 记录收到的事件，任何事件都会刷新 `last_seen`
+    pub fn observe(&self, event: &Event) {
+        let self_id = event.self_id();
+        let mut bots = self.0.bots.borrow_mut();
+        let bot = bots.entry(self_id).or_insert_with(|| BotLiveness {
+            liveness: Liveness::Offline,
+            last_seen: Instant::now(),
+            interval: None,
+            status: None,
+            generation: 0,
+        });
+        bot.last_seen = Instant::now();
+
+        let liveness = match event {
+            Event::MetaEvent(MetaEvent {
+                meta: Meta::Lifecycle(Lifecycle::Disable),
+                ..
+            }) => Liveness::Offline,
+            Event::MetaEvent(MetaEvent {
+                meta: Meta::Heartbeat(Heartbeat { status, interval }),
+                ..
+            }) => {
+                bot.status = Some(status.clone());
+                bot.interval = u64::try_from(*interval).ok().map(Duration::from_millis);
+                bot.generation = bot.generation.wrapping_add(1);
+                if let Some(interval) = bot.interval {
+                    self.watch(self_id, bot.generation, interval);
+                }
+                match status.online {
+                    Some(false) => Liveness::Offline,
+                    _ => Liveness::Online,
+                }
+            }
+            _ => Liveness::Online,
+        };
+        self.set(self_id, bot, liveness);
+    }
+
+    fn watch(&self, self_id: i64, generation: u64, interval: Duration) {
+        let timeout = u32::try_from((interval * self.0.tolerance).as_millis()).unwrap_or(u32::MAX);
+        let tracker = self.clone();
+        rt::spawn(async move {
+            sleep(Millis(timeout)).await;
+            let mut bots = tracker.0.bots.borrow_mut();
+            let Some(bot) = bots.get_mut(&self_id) else {
+                return;
+            };
+            if bot.generation == generation {
+                tracing::warn!(self_id, "heartbeat timed out");
+                tracker.set(self_id, bot, Liveness::Offline);
+            }
+        });
+    }
+}
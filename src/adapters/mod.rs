@@ -1,7 +1,13 @@
 pub mod auth;
 pub mod backward_ws;
+pub mod caller;
 pub mod forward_ws;
 pub mod http;
 pub mod http_post;
+pub mod liveness;
+pub mod retry;
+pub mod send_queue;
+pub mod timeout;
+pub mod ws;
 
 mod http_ws;
@@ -0,0 +1,325 @@
+use std::{
+    borrow::Cow,
+    cell::Cell,
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use ntex::time::sleep;
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::Value;
+
+use crate::error::{Category, Error, ErrorKind, Transport};
+
+use super::{
+    caller::ApiCaller,
+    http::{
+        ActionClass, Entry, ExecError, JsonReq, Mode, RawAction, Response, Retcode, SendError,
+        SendErrorKind,
+    },
+    ws::WsExecError,
+};
+
+/// 重试间隔按 `factor` 指数增长，不超过 `max`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    pub base: Duration,
+    pub max: Duration,
+    pub factor: f64,
+    /// 每次间隔随机减少的最大比例，0 到 1
+    pub jitter: f64,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            base: Duration::from_millis(200),
+            max: Duration::from_secs(5),
+            factor: 2.0,
+            jitter: 0.5,
+        }
+    }
+}
+
+impl Backoff {
+    /// 第 `retry` 次重试前的间隔，`random` 在 0 到 1 之间
+    ///
+    /// 按秒计算后再截断到 `max`，重试次数很大时不会溢出
+    pub fn delay(&self, retry: u32, random: f64) -> Duration {
+        let max = self.max.as_secs_f64();
+        let growth = self.factor.max(1.0).powi(retry.min(i32::MAX as u32) as i32);
+        // `base` 为零且增长为无穷大时得到 NaN
+        let delay = match self.base.as_secs_f64() * growth {
+            delay if delay.is_nan() => 0.0,
+            delay => delay.min(max),
+        };
+        let delay = delay * (1.0 - unit(self.jitter) * unit(random));
+        Duration::try_from_secs_f64(delay).unwrap_or(self.max)
+    }
+}
+
+/// 截断到 0 到 1，NaN 视为 0
+fn unit(value: f64) -> f64 {
+    if value.is_nan() {
+        0.0
+    } else {
+        value.clamp(0.0, 1.0)
+    }
+}
+
+/// 动作能否安全地重复执行
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Idempotency {
+    /// 任意传输失败或超时都可以重试
+    Safe,
+    /// 请求可能已送达，只在连接尚未建立时重试
+    Unsafe,
+}
+
+/// 按 `retcode` 重试，与传输失败分开计数
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RetcodeRetry {
+    pub retcodes: Vec<Retcode>,
+    pub max_retries: u32,
+    /// 非幂等动作也按 `retcode` 重试
+    pub unsafe_actions: bool,
+}
+
+/// `get_*`、`can_*` 视为幂等，其余动作只在连接失败时重试
+///
+/// 默认不按 `retcode` 重试
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// 传输失败后最多重试的次数
+    pub max_retries: u32,
+    pub backoff: Backoff,
+    pub retcode: RetcodeRetry,
+    pub actions: HashMap<Cow<'static, str>, Idempotency>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            backoff: Backoff::default(),
+            retcode: RetcodeRetry::default(),
+            actions: HashMap::new(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: u32, backoff: Backoff) -> Self {
+        Self {
+            max_retries,
+            backoff,
+            ..Self::default()
+        }
+    }
+
+    pub fn retcode(mut self, retcode: RetcodeRetry) -> Self {
+        self.retcode = retcode;
+        self
+    }
+
+    /// 覆盖按名称前缀得到的分类
+    pub fn action(
+        mut self,
+        action: impl Into<Cow<'static, str>>,
+        idempotency: Idempotency,
+    ) -> Self {
+        self.actions.insert(action.into(), idempotency);
+        self
+    }
+
+    pub fn idempotency(&self, action: &str) -> Idempotency {
+        self.actions
+            .get(action)
+            .copied()
+            .unwrap_or(match ActionClass::of(action) {
+                ActionClass::Query => Idempotency::Safe,
+                _ => Idempotency::Unsafe,
+            })
+    }
+
+    fn retries_error(&self, idempotency: Idempotency, error: &Error) -> bool {
+        match idempotency {
+            Idempotency::Safe => {
+                matches!(error.category(), Category::Transport | Category::Timeout)
+            }
+            Idempotency::Unsafe => is_connect(error),
+        }
+    }
+
+    fn retries_retcode(&self, idempotency: Idempotency, retcode: i32) -> bool {
+        (idempotency == Idempotency::Safe || self.retcode.unsafe_actions)
+            && self.retcode.retcodes.contains(&Retcode::from(retcode))
+    }
+}
+
+/// 请求尚未发出的失败
+fn is_connect(error: &Error) -> bool {
+    matches!(
+        error.kind,
+        ErrorKind::Http(ExecError::Send(SendError {
+            kind: SendErrorKind::Connect,
+            ..
+        })) | ErrorKind::Ws(WsExecError::NoApiConnection)
+    )
+}
+
+/// 按 [`RetryPolicy`] 重试失败调用的 [`ApiCaller`]
+#[derive(Debug, Clone)]
+pub struct Retry<C> {
+    inner: C,
+    policy: RetryPolicy,
+    seed: Cell<u64>,
+}
+
+impl<C: ApiCaller> Retry<C> {
+    pub fn new(inner: C, policy: RetryPolicy) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        Self {
+            inner,
+            policy,
+            seed: Cell::new(seed | 1),
+        }
+    }
+
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    pub fn policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+
+    /// xorshift，只用于抖动
+    fn random(&self) -> f64 {
+        let mut x = self.seed.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.seed.set(x);
+        (x >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    async fn retry<O, E, F>(
+        &self,
+        action: &str,
+        mut call: impl FnMut() -> F,
+    ) -> Result<Response<O, E>, Error>
+    where
+        F: Future<Output = Result<Response<O, E>, Error>>,
+    {
+        let idempotency = self.policy.idempotency(action);
+        let (mut errors, mut retcodes) = (0, 0);
+        loop {
+            let retry = match call().await {
+                Ok(response)
+                    if retcodes < self.policy.retcode.max_retries
+                        && self.policy.retries_retcode(idempotency, response.retcode) =>
+                {
+                    retcodes += 1;
+                    tracing::debug!(action, retcode = response.retcode, "retrying action");
+                    retcodes
+                }
+                Err(e)
+                    if errors < self.policy.max_retries
+                        && self.policy.retries_error(idempotency, &e) =>
+                {
+                    errors += 1;
+                    tracing::debug!(action, error = %e, "retrying action");
+                    errors
+                }
+                result => return result,
+            };
+            sleep(self.policy.backoff.delay(retry - 1, self.random())).await;
+        }
+    }
+}
+
+/// 借用原请求以便重复发送
+struct Replay<'a, T>(&'a T);
+
+impl<T: Serialize> Serialize for Replay<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<T: Entry> Entry for Replay<'_, T> {
+    type Output = T::Output;
+    const ENTRY: &'static str = T::ENTRY;
+}
+
+impl<T: JsonReq> JsonReq for Replay<'_, T> {}
+
+impl<C: ApiCaller> ApiCaller for Retry<C> {
+    fn transport(&self) -> Option<Transport> {
+        self.inner.transport()
+    }
+
+    async fn call<T, E>(&self, data: T, mode: Mode) -> Result<Response<T::Output, E>, Error>
+    where
+        T: JsonReq,
+        E: for<'de> Deserialize<'de>,
+    {
+        self.retry(T::ENTRY, || {
+            self.inner.call::<Replay<'_, T>, E>(Replay(&data), mode)
+        })
+        .await
+    }
+
+    async fn call_raw<P: Serialize>(
+        &self,
+        data: RawAction<P>,
+        mode: Mode,
+    ) -> Result<Response<Value, Value>, Error> {
+        self.retry(&data.action, || {
+            self.inner
+                .call_raw(RawAction::new(data.action.clone(), &data.params), mode)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_is_capped_for_many_retries() {
+        let backoff = Backoff::default();
+        assert_eq!(backoff.delay(0, 0.0), backoff.base);
+        assert_eq!(backoff.delay(70, 0.0), backoff.max);
+        assert_eq!(backoff.delay(u32::MAX, 0.0), backoff.max);
+    }
+
+    #[test]
+    fn delay_ignores_nan() {
+        let backoff = Backoff {
+            jitter: f64::NAN,
+            ..Backoff::default()
+        };
+        assert_eq!(backoff.delay(1, 0.5), Duration::from_millis(400));
+        assert_eq!(
+            Backoff::default().delay(1, f64::NAN),
+            Duration::from_millis(400)
+        );
+
+        let zero = Backoff {
+            base: Duration::ZERO,
+            ..Backoff::default()
+        };
+        assert_eq!(zero.delay(u32::MAX, 0.0), Duration::ZERO);
+    }
+
+    #[test]
+    fn jitter_shortens_delay() {
+        let backoff = Backoff::default();
+        assert_eq!(backoff.delay(1, 1.0), Duration::from_millis(200));
+    }
+}
@@ -0,0 +1,488 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, VecDeque},
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use ntex::{
+    channel::{mpsc, oneshot},
+    rt,
+    time::sleep,
+    util::select,
+};
+
+use crate::{
+    api::{SendGroupMessageRequestJson, SendMessageRequestJson},
+    error::{Error, ErrorKind},
+    models::message::JsonMsgSend,
+};
+
+use super::{caller::ApiCaller, http::Mode};
+
+/// 令牌桶的速率，每 `interval` 补充一个令牌，最多积攒 `burst` 个
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rate {
+    pub burst: u32,
+    pub interval: Duration,
+}
+
+impl Rate {
+    pub const fn new(burst: u32, interval: Duration) -> Self {
+        Self { burst, interval }
+    }
+}
+
+/// 默认值偏保守，连续刷屏时大约每 2 秒向同一个群发一条
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendLimits {
+    /// 整个账号
+    pub account: Rate,
+    /// 每个群
+    pub group: Rate,
+    /// 每个私聊
+    pub private: Rate,
+}
+
+impl Default for SendLimits {
+    fn default() -> Self {
+        Self {
+            account: Rate::new(10, Duration::from_millis(500)),
+            group: Rate::new(3, Duration::from_secs(2)),
+            private: Rate::new(3, Duration::from_secs(1)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Target {
+    Private(i64),
+    Group(i64),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// 回复，先于同一账号所有普通消息发送
+    Reply,
+    #[default]
+    Normal,
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    rate: Rate,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: Rate, now: Instant) -> Self {
+        Self {
+            rate,
+            tokens: rate.burst.max(1).into(),
+            last: now,
+        }
+    }
+
+    fn capacity(&self) -> f64 {
+        self.rate.burst.max(1).into()
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let interval = self.rate.interval.as_secs_f64();
+        let refilled = if interval > 0.0 {
+            now.duration_since(self.last).as_secs_f64() / interval
+        } else {
+            self.capacity()
+        };
+        self.tokens = (self.tokens + refilled).min(self.capacity());
+        self.last = now;
+    }
+
+    /// 距离下一个令牌的时间，已有令牌时为零
+    fn wait(&mut self, now: Instant) -> Duration {
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            self.rate.interval.mul_f64(1.0 - self.tokens)
+        }
+    }
+
+    fn take(&mut self) {
+        self.tokens -= 1.0;
+    }
+
+    fn is_full(&self) -> bool {
+        self.tokens >= self.capacity()
+    }
+}
+
+#[derive(Debug)]
+struct Job {
+    seq: u64,
+    message: JsonMsgSend,
+    tx: oneshot::Sender<Result<i32, Error>>,
+}
+
+#[derive(Debug)]
+struct TargetQueue {
+    bucket: TokenBucket,
+    reply: VecDeque<Job>,
+    normal: VecDeque<Job>,
+    /// 同一目标同时只发送一条，保证顺序
+    sending: bool,
+}
+
+impl TargetQueue {
+    /// 丢弃调用方已不再等待的任务
+    fn prune(&mut self) {
+        self.reply.retain(|job| !job.tx.is_canceled());
+        self.normal.retain(|job| !job.tx.is_canceled());
+    }
+
+    fn front(&self) -> Option<(Priority, u64)> {
+        match (self.reply.front(), self.normal.front()) {
+            (Some(job), _) => Some((Priority::Reply, job.seq)),
+            (None, Some(job)) => Some((Priority::Normal, job.seq)),
+            (None, None) => None,
+        }
+    }
+
+    fn pop(&mut self) -> Option<Job> {
+        self.reply.pop_front().or_else(|| self.normal.pop_front())
+    }
+}
+
+enum Next {
+    Send(Target, Job),
+    Wait(Option<Duration>),
+    Idle,
+}
+
+/// 一个账号的发送队列，按令牌桶限制账号、群与私聊的发送速率
+///
+/// 同一目标同一优先级的消息按入队顺序逐条发送，回复优先于普通消息
+#[derive(Debug)]
+pub struct SendQueue<C>(Rc<SendQueueInner<C>>);
+
+impl<C> Clone for SendQueue<C> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+#[derive(Debug)]
+struct SendQueueInner<C> {
+    caller: C,
+    limits: SendLimits,
+    account: RefCell<TokenBucket>,
+    targets: RefCell<HashMap<Target, TargetQueue>>,
+    next_seq: Cell<u64>,
+    running: Cell<bool>,
+    wake_tx: mpsc::Sender<()>,
+    wake_rx: mpsc::Receiver<()>,
+}
+
+/// 等待消息发出，得到 `message_id`
+///
+/// 在发出前丢弃时，这条消息不会再发送
+#[derive(Debug)]
+pub struct PendingSend(oneshot::Receiver<Result<i32, Error>>);
+
+impl Future for PendingSend {
+    type Output = Result<i32, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match Pin::new(&mut self.0).poll(cx) {
+            Poll::Ready(Ok(result)) => Poll::Ready(result),
+            Poll::Ready(Err(_)) => Poll::Ready(Err(ErrorKind::Canceled.into())),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<C: ApiCaller + 'static> SendQueue<C> {
+    pub fn new(caller: C, limits: SendLimits) -> Self {
+        let (wake_tx, wake_rx) = mpsc::channel();
+        Self(Rc::new(SendQueueInner {
+            caller,
+            limits,
+            account: RefCell::new(TokenBucket::new(limits.account, Instant::now())),
+            targets: RefCell::default(),
+            next_seq: Cell::new(0),
+            running: Cell::new(false),
+            wake_tx,
+            wake_rx,
+        }))
+    }
+
+    pub fn send(
+        &self,
+        target: Target,
+        message: impl Into<JsonMsgSend>,
+        priority: Priority,
+    ) -> PendingSend {
+        let (tx, rx) = oneshot::channel();
+        let seq = self.0.next_seq.get();
+        self.0.next_seq.set(seq.wrapping_add(1));
+        let job = Job {
+            seq,
+            message: message.into(),
+            tx,
+        };
+
+        let rate = match target {
+            Target::Private(_) => self.0.limits.private,
+            Target::Group(_) => self.0.limits.group,
+        };
+        let mut targets = self.0.targets.borrow_mut();
+        let queue = targets.entry(target).or_insert_with(|| TargetQueue {
+            bucket: TokenBucket::new(rate, Instant::now()),
+            reply: VecDeque::new(),
+            normal: VecDeque::new(),
+            sending: false,
+        });
+        match priority {
+            Priority::Reply => queue.reply.push_back(job),
+            Priority::Normal => queue.normal.push_back(job),
+        }
+        drop(targets);
+
+        self.wake();
+        PendingSend(rx)
+    }
+
+    pub fn reply(&self, target: Target, message: impl Into<JsonMsgSend>) -> PendingSend {
+        self.send(target, message, Priority::Reply)
+    }
+
+    fn wake(&self) {
+        if self.0.running.replace(true) {
+            let _ = self.0.wake_tx.send(());
+        } else {
+            rt::spawn(self.clone().run());
+        }
+    }
+
+    async fn run(self) {
+        loop {
+            match self.next() {
+                Next::Send(target, job) => self.dispatch(target, job),
+                Next::Wait(Some(wait)) => {
+                    select(sleep(wait), self.0.wake_rx.recv()).await;
+                }
+                Next::Wait(None) => {
+                    self.0.wake_rx.recv().await;
+                }
+                Next::Idle => {
+                    self.0.running.set(false);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// 在账号与目标都有令牌的任务中，选回复优先、入队最早的一条
+    fn next(&self) -> Next {
+        let now = Instant::now();
+        let mut account = self.0.account.borrow_mut();
+        let mut targets = self.0.targets.borrow_mut();
+        let account_wait = account.wait(now);
+
+        let mut best: Option<((Priority, u64), Target)> = None;
+        let mut wait: Option<Duration> = None;
+        let mut sending = false;
+        targets.retain(|target, queue| {
+            queue.prune();
+            if queue.sending {
+                sending = true;
+                return true;
+            }
+            let target_wait = queue.bucket.wait(now);
+            let Some(key) = queue.front() else {
+                return !queue.bucket.is_full();
+            };
+            match target_wait.max(account_wait) {
+                Duration::ZERO => {
+                    if best.is_none_or(|(best, _)| key < best) {
+                        best = Some((key, *target));
+                    }
+                }
+                target_wait => wait = Some(wait.map_or(target_wait, |wait| wait.min(target_wait))),
+            }
+            true
+        });
+
+        if let Some((_, target)) = best
+            && let Some(queue) = targets.get_mut(&target)
+            && let Some(job) = queue.pop()
+        {
+            account.take();
+            queue.bucket.take();
+            queue.sending = true;
+            return Next::Send(target, job);
+        }
+        match wait {
+            Some(wait) => Next::Wait(Some(wait)),
+            None if sending => Next::Wait(None),
+            // 令牌未补满的空闲目标留到下次运行时清理
+            None => Next::Idle,
+        }
+    }
+
+    fn dispatch(&self, target: Target, job: Job) {
+        let queue = self.clone();
+        rt::spawn(async move {
+            let result = queue.send_now(target, job.message).await;
+            let _ = job.tx.send(result);
+            if let Some(target) = queue.0.targets.borrow_mut().get_mut(&target) {
+                target.sending = false;
+            }
+            let _ = queue.0.wake_tx.send(());
+        });
+    }
+
+    async fn send_now(&self, target: Target, message: JsonMsgSend) -> Result<i32, Error> {
+        let caller = &self.0.caller;
+        let message_id = match target {
+            Target::Private(user_id) => {
+                let data = SendMessageRequestJson {
+                    user_id,
+                    message,
+                    auto_escape: false,
+                };
+                caller
+                    .invoke(data, Mode::Default)
+                    .await?
+                    .into_ok()?
+                    .message_id
+            }
+            Target::Group(group_id) => {
+                let data = SendGroupMessageRequestJson {
+                    group_id,
+                    message,
+                    auto_escape: false,
+                };
+                caller
+                    .invoke(data, Mode::Default)
+                    .await?
+                    .into_ok()?
+                    .message_id
+            }
+        };
+        Ok(message_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Serialize};
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::{
+        adapters::http::{JsonReq, RawAction, Response},
+        models::builder::MessageBuilder,
+    };
+
+    /// 记录发出的纯文本，同一时间只允许一条在发送
+    #[derive(Debug, Clone, Default)]
+    struct Recorder {
+        sent: Rc<RefCell<Vec<String>>>,
+        in_flight: Rc<Cell<u32>>,
+        max_in_flight: Rc<Cell<u32>>,
+    }
+
+    impl ApiCaller for Recorder {
+        async fn call<T, E>(&self, data: T, _mode: Mode) -> Result<Response<T::Output, E>, Error>
+        where
+            T: JsonReq,
+            E: for<'de> Deserialize<'de>,
+        {
+            let data = serde_json::to_value(&data).unwrap();
+            let text = data["message"][0]["data"]["text"].as_str().unwrap();
+            self.sent.borrow_mut().push(text.to_owned());
+            let message_id = self.sent.borrow().len();
+
+            self.in_flight.set(self.in_flight.get() + 1);
+            self.max_in_flight
+                .set(self.max_in_flight.get().max(self.in_flight.get()));
+            sleep(Duration::from_millis(5)).await;
+            self.in_flight.set(self.in_flight.get() - 1);
+
+            let response = json!({
+                "status": "ok",
+                "retcode": 0,
+                "data": { "message_id": message_id },
+            });
+            Ok(serde_json::from_value(response).unwrap())
+        }
+
+        async fn call_raw<P: Serialize>(
+            &self,
+            _data: RawAction<P>,
+            _mode: Mode,
+        ) -> Result<Response<Value, Value>, Error> {
+            unreachable!()
+        }
+    }
+
+    fn text(text: &str) -> JsonMsgSend {
+        MessageBuilder::new().text(text).build()
+    }
+
+    fn queue(recorder: &Recorder) -> SendQueue<Recorder> {
+        let rate = Rate::new(10, Duration::from_millis(1));
+        let limits = SendLimits {
+            account: rate,
+            group: rate,
+            private: rate,
+        };
+        SendQueue::new(recorder.clone(), limits)
+    }
+
+    #[ntex::test]
+    async fn reply_preempts_normal_messages() {
+        let recorder = Recorder::default();
+        let queue = queue(&recorder);
+        let first = queue.send(Target::Group(1), text("n1"), Priority::Normal);
+        let second = queue.send(Target::Group(2), text("n2"), Priority::Normal);
+        let reply = queue.reply(Target::Group(3), text("r"));
+
+        reply.await.unwrap();
+        first.await.unwrap();
+        second.await.unwrap();
+        assert_eq!(*recorder.sent.borrow(), ["r", "n1", "n2"]);
+    }
+
+    #[ntex::test]
+    async fn same_target_is_sent_in_order_one_at_a_time() {
+        let recorder = Recorder::default();
+        let queue = queue(&recorder);
+        let pending = (0..5)
+            .map(|i| queue.send(Target::Private(1), text(&i.to_string()), Priority::Normal))
+            .collect::<Vec<_>>();
+
+        for pending in pending {
+            pending.await.unwrap();
+        }
+        assert_eq!(*recorder.sent.borrow(), ["0", "1", "2", "3", "4"]);
+        assert_eq!(recorder.max_in_flight.get(), 1);
+    }
+
+    #[ntex::test]
+    async fn dropped_jobs_are_not_sent() {
+        let recorder = Recorder::default();
+        let queue = queue(&recorder);
+        drop(queue.send(Target::Group(1), text("dropped"), Priority::Normal));
+        let kept = queue.send(Target::Group(1), text("kept"), Priority::Normal);
+
+        assert_eq!(kept.await.unwrap(), 1);
+        assert_eq!(*recorder.sent.borrow(), ["kept"]);
+    }
+}
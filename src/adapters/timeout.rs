@@ -0,0 +1,132 @@
+use std::{borrow::Cow, collections::HashMap, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{Error, ErrorKind, Transport};
+
+use super::{
+    caller::ApiCaller,
+    http::{JsonReq, Mode, RawAction, Response},
+};
+
+/// 为每个动作选择时限，按动作名、默认值的顺序查找，都没有时不限时
+///
+/// 包装 [`HttpCaller::without_client_timeout`](super::caller::HttpCaller::without_client_timeout)
+/// 或 WebSocket 连接时，应使用带默认值的 [`TimeoutPolicy::new`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TimeoutPolicy {
+    pub default: Option<Duration>,
+    pub actions: HashMap<Cow<'static, str>, Duration>,
+}
+
+impl TimeoutPolicy {
+    pub fn new(default: Duration) -> Self {
+        Self {
+            default: Some(default),
+            ..Self::default()
+        }
+    }
+
+    pub fn action(mut self, action: impl Into<Cow<'static, str>>, limit: Duration) -> Self {
+        self.actions.insert(action.into(), limit);
+        self
+    }
+
+    pub fn timeout_for(&self, action: &str) -> Option<Duration> {
+        self.actions.get(action).copied().or(self.default)
+    }
+}
+
+/// 在 `limit` 内等待 `future`，超时时丢弃它并返回 [`ErrorKind::Timeout`]
+///
+/// 丢弃即取消：HTTP 请求随之中断，WebSocket 调用释放 `echo`
+pub async fn within<T>(
+    action: impl Into<Cow<'static, str>>,
+    limit: Duration,
+    future: impl Future<Output = Result<T, Error>>,
+) -> Result<T, Error> {
+    match ntex::time::timeout(limit, future).await {
+        Ok(result) => result,
+        Err(()) => Err(Error::new(ErrorKind::Timeout(Some(limit))).with_action(action)),
+    }
+}
+
+/// 按 [`TimeoutPolicy`] 限制每次调用时长的 [`ApiCaller`]
+///
+/// 与 [`Retry`](super::retry::Retry) 组合时，`Retry<Timeout<C>>` 对每次尝试限时，
+/// `Timeout<Retry<C>>` 对包括重试在内的整个调用限时
+#[derive(Debug, Clone)]
+pub struct Timeout<C> {
+    inner: C,
+    policy: TimeoutPolicy,
+}
+
+impl<C: ApiCaller> Timeout<C> {
+    pub fn new(inner: C, policy: TimeoutPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    pub fn policy(&self) -> &TimeoutPolicy {
+        &self.policy
+    }
+
+    /// 本次调用使用 `limit`，不查 [`TimeoutPolicy`]
+    pub async fn call_within<T, E>(
+        &self,
+        data: T,
+        mode: Mode,
+        limit: Duration,
+    ) -> Result<Response<T::Output, E>, Error>
+    where
+        T: JsonReq,
+        E: for<'de> Deserialize<'de>,
+    {
+        within(T::ENTRY, limit, self.inner.call::<T, E>(data, mode))
+            .await
+            .map_err(|e| e.or_transport(self.inner.transport()))
+    }
+
+    pub async fn call_raw_within<P: Serialize>(
+        &self,
+        data: RawAction<P>,
+        mode: Mode,
+        limit: Duration,
+    ) -> Result<Response<Value, Value>, Error> {
+        within(data.action.clone(), limit, self.inner.call_raw(data, mode))
+            .await
+            .map_err(|e| e.or_transport(self.inner.transport()))
+    }
+}
+
+impl<C: ApiCaller> ApiCaller for Timeout<C> {
+    fn transport(&self) -> Option<Transport> {
+        self.inner.transport()
+    }
+
+    async fn call<T, E>(&self, data: T, mode: Mode) -> Result<Response<T::Output, E>, Error>
+    where
+        T: JsonReq,
+        E: for<'de> Deserialize<'de>,
+    {
+        match self.policy.timeout_for(T::ENTRY) {
+            Some(limit) => self.call_within(data, mode, limit).await,
+            None => self.inner.call(data, mode).await,
+        }
+    }
+
+    async fn call_raw<P: Serialize>(
+        &self,
+        data: RawAction<P>,
+        mode: Mode,
+    ) -> Result<Response<Value, Value>, Error> {
+        match self.policy.timeout_for(&data.action) {
+            Some(limit) => self.call_raw_within(data, mode, limit).await,
+            None => self.inner.call_raw(data, mode).await,
+        }
+    }
+}
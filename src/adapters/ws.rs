@@ -0,0 +1,502 @@
+use std::{
+    borrow::Cow,
+    cell::{Cell, RefCell},
+    collections::{HashMap, VecDeque},
+    error::Error,
+    fmt::{self, Display},
+    future::poll_fn,
+    rc::Rc,
+    task::Poll,
+};
+
+use ntex::{
+    channel::oneshot,
+    io::IoRef,
+    task::LocalWaker,
+    util::Bytes,
+    ws::{Codec, Frame, Item, Message, error::ProtocolError},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{PipeOps, models::event::Event};
+
+use super::http::{Entry, Mode, RawAction, Response};
+
+/// 默认的单帧上限，16 MiB
+///
+/// 大群的成员列表、合并转发等回复可能远超 ntex 默认的 64 KiB。
+/// 单帧超过上限时连接断开，分片消息拼接后超过上限时只丢弃这条消息
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// 每个 [`WsConn`] 最多缓存的未取走事件，超出时丢弃新事件
+///
+/// 只用作 [`ApiCaller`](super::caller::ApiCaller) 而从不调用 [`WsConn::recv`] 时，事件不会无限堆积
+pub const EVENT_BUFFER: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WsRole {
+    Universal,
+    Api,
+    Event,
+}
+
+impl WsRole {
+    pub fn to_path(self) -> &'static str {
+        match self {
+            WsRole::Universal => "/",
+            WsRole::Api => "/api",
+            WsRole::Event => "/event",
+        }
+    }
+
+    /// `X-Client-Role` 的取值
+    pub fn to_header(self) -> &'static str {
+        match self {
+            WsRole::Universal => "Universal",
+            WsRole::Api => "API",
+            WsRole::Event => "Event",
+        }
+    }
+
+    pub fn from_header(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "universal" => Some(WsRole::Universal),
+            "api" => Some(WsRole::Api),
+            "event" => Some(WsRole::Event),
+            _ => None,
+        }
+    }
+
+    pub const fn has_api(self) -> bool {
+        matches!(self, WsRole::Universal | WsRole::Api)
+    }
+
+    pub const fn has_event(self) -> bool {
+        matches!(self, WsRole::Universal | WsRole::Event)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ActionFrame<'a, T> {
+    pub action: String,
+    pub params: &'a T,
+    pub echo: String,
+}
+
+#[derive(Debug)]
+pub enum WsExecError {
+    Encode(serde_json::Error),
+    Send(ProtocolError),
+    Decode(serde_json::Error),
+    /// 当前没有可以发送 API 的连接
+    NoApiConnection,
+    /// 连接在收到回复前断开
+    Closed,
+}
+
+impl Display for WsExecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            WsExecError::Encode(_) => "failed to encode action frame",
+            WsExecError::Send(_) => "failed to send action frame",
+            WsExecError::Decode(_) => "failed to decode response",
+            WsExecError::NoApiConnection => "no connection accepts api calls",
+            WsExecError::Closed => "connection closed before response",
+        })
+    }
+}
+
+impl Error for WsExecError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            WsExecError::Encode(e) | WsExecError::Decode(e) => Some(e),
+            WsExecError::Send(e) => Some(e),
+            WsExecError::NoApiConnection | WsExecError::Closed => None,
+        }
+    }
+}
+
+/// 与 [`ntex::ws::WsSink`] 相同，ntex 没有公开它的构造函数，而反向连接需要用自己的 [`Codec`]
+#[derive(Debug, Clone)]
+pub(crate) struct FrameSink {
+    io: IoRef,
+    codec: Codec,
+}
+
+impl FrameSink {
+    pub(crate) fn new(io: IoRef, codec: Codec) -> Self {
+        Self { io, codec }
+    }
+
+    pub(crate) fn io(&self) -> &IoRef {
+        &self.io
+    }
+
+    fn send(&self, message: Message) -> Result<(), ProtocolError> {
+        let close = matches!(message, Message::Close(_)) && self.codec.is_closed();
+        self.io.encode(message, &self.codec)?;
+        if close {
+            self.io.close();
+        }
+        Ok(())
+    }
+}
+
+/// 调用结束或被丢弃时释放 `echo`，之后的回复按未知 `echo` 丢弃
+struct EchoSlot<'a> {
+    pending: &'a RefCell<HashMap<String, oneshot::Sender<Value>>>,
+    echo: String,
+}
+
+impl Drop for EchoSlot<'_> {
+    fn drop(&mut self) {
+        self.pending.borrow_mut().remove(&self.echo);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WsCaller(Rc<WsCallerInner>);
+
+#[derive(Debug)]
+struct WsCallerInner {
+    sink: FrameSink,
+    pending: RefCell<HashMap<String, oneshot::Sender<Value>>>,
+    next_echo: Cell<u64>,
+}
+
+impl WsCaller {
+    pub(crate) fn new(sink: FrameSink) -> Self {
+        Self(Rc::new(WsCallerInner {
+            sink,
+            pending: RefCell::default(),
+            next_echo: Cell::new(0),
+        }))
+    }
+
+    fn next_echo(&self) -> String {
+        let echo = self.0.next_echo.get();
+        self.0.next_echo.set(echo.wrapping_add(1));
+        echo.to_string()
+    }
+
+    pub async fn call<T, E>(
+        &self,
+        data: T,
+        mode: Mode,
+    ) -> Result<Response<T::Output, E>, WsExecError>
+    where
+        T: Entry,
+        E: for<'de> Deserialize<'de>,
+    {
+        self.call_action(T::ENTRY.into(), &data, mode).await
+    }
+
+    pub async fn call_raw<P: Serialize>(
+        &self,
+        data: RawAction<P>,
+        mode: Mode,
+    ) -> Result<Response<Value, Value>, WsExecError> {
+        self.call_action(data.action, &data.params, mode).await
+    }
+
+    async fn call_action<P, O, E>(
+        &self,
+        action: Cow<'static, str>,
+        params: &P,
+        mode: Mode,
+    ) -> Result<Response<O, E>, WsExecError>
+    where
+        P: Serialize,
+        O: for<'de> Deserialize<'de>,
+        E: for<'de> Deserialize<'de>,
+    {
+        let echo = self.next_echo();
+        let frame = ActionFrame {
+            action: format!("{}{}", action, mode.to_suffix()),
+            params,
+            echo: echo.clone(),
+        }
+        .pipe(|frame| serde_json::to_string(&frame))
+        .map_err(WsExecError::Encode)?;
+
+        let (tx, rx) = oneshot::channel();
+        self.0.pending.borrow_mut().insert(echo.clone(), tx);
+        let _slot = EchoSlot {
+            pending: &self.0.pending,
+            echo,
+        };
+        self.0
+            .sink
+            .send(Message::Text(frame.into()))
+            .map_err(WsExecError::Send)?;
+
+        rx.await
+            .map_err(|_| WsExecError::Closed)?
+            .pipe(serde_json::from_value::<Response<O, E>>)
+            .map_err(WsExecError::Decode)
+            .map(|response| Response {
+                action,
+                mode,
+                ..response
+            })
+    }
+
+    /// 按 `echo` 把回复交给等待中的调用，不是回复的帧原样返回
+    pub(crate) fn dispatch(&self, value: Value) -> Option<Value> {
+        let echo = match value.get("echo") {
+            None | Some(Value::Null) => return Some(value),
+            Some(Value::String(echo)) => echo.clone(),
+            Some(echo) => echo.to_string(),
+        };
+        match self.0.pending.borrow_mut().remove(&echo) {
+            Some(tx) => {
+                let _ = tx.send(value);
+            }
+            None => tracing::warn!(echo, "dropping reply with unknown echo"),
+        }
+        None
+    }
+
+    /// 丢弃所有等待中的调用，它们会得到 [`WsExecError::Closed`]
+    pub(crate) fn close(&self) {
+        self.0.pending.borrow_mut().clear();
+    }
+
+    pub(crate) fn same(&self, other: &WsCaller) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// 一个账号的 WebSocket 连接，API 与事件可以来自同一条或不同的连接
+#[derive(Debug, Clone)]
+pub struct WsConn(Rc<WsConnInner>);
+
+#[derive(Debug)]
+struct WsConnInner {
+    self_id: Option<i64>,
+    caller: RefCell<Option<WsCaller>>,
+    events: RefCell<VecDeque<Event>>,
+    /// 当前接收事件的连接数，`None` 表示还没有连上过
+    event_conns: Cell<Option<usize>>,
+    /// 断开后会由实现重新连上，`recv` 不因断开结束
+    reconnects: bool,
+    /// 有新事件或事件连接全部断开时唤醒 `recv`
+    waker: LocalWaker,
+}
+
+impl WsConn {
+    /// 正向连接，断开后不会恢复
+    pub(crate) fn forward() -> Self {
+        Self::new(None, false)
+    }
+
+    /// 反向连接，同一账号重连时沿用
+    pub(crate) fn backward(self_id: i64) -> Self {
+        Self::new(Some(self_id), true)
+    }
+
+    fn new(self_id: Option<i64>, reconnects: bool) -> Self {
+        Self(Rc::new(WsConnInner {
+            self_id,
+            reconnects,
+            caller: RefCell::default(),
+            events: RefCell::default(),
+            event_conns: Cell::new(None),
+            waker: LocalWaker::new(),
+        }))
+    }
+
+    pub fn self_id(&self) -> Option<i64> {
+        self.0.self_id
+    }
+
+    pub fn has_api(&self) -> bool {
+        self.0.caller.borrow().is_some()
+    }
+
+    pub async fn call<T, E>(
+        &self,
+        data: T,
+        mode: Mode,
+    ) -> Result<Response<T::Output, E>, WsExecError>
+    where
+        T: Entry,
+        E: for<'de> Deserialize<'de>,
+    {
+        let caller = self
+            .0
+            .caller
+            .borrow()
+            .clone()
+            .ok_or(WsExecError::NoApiConnection)?;
+        caller.call(data, mode).await
+    }
+
+    pub async fn call_raw<P: Serialize>(
+        &self,
+        data: RawAction<P>,
+        mode: Mode,
+    ) -> Result<Response<Value, Value>, WsExecError> {
+        let caller = self
+            .0
+            .caller
+            .borrow()
+            .clone()
+            .ok_or(WsExecError::NoApiConnection)?;
+        caller.call_raw(data, mode).await
+    }
+
+    /// 等待下一条事件
+    ///
+    /// 正向连接在所有事件连接都关闭且已收到的事件取完后返回 `None`。
+    /// 反向连接重连时沿用同一个 [`WsConn`]，断开期间保持等待，从不返回 `None`
+    pub async fn recv(&self) -> Option<Event> {
+        poll_fn(|cx| {
+            if let Some(event) = self.0.events.borrow_mut().pop_front() {
+                return Poll::Ready(Some(event));
+            }
+            if !self.0.reconnects && self.0.event_conns.get() == Some(0) {
+                return Poll::Ready(None);
+            }
+            self.0.waker.register(cx.waker());
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// 开始处理一条新连接，返回处理这条连接的帧的函数
+    ///
+    /// 分片消息拼接后超过 `max_size` 时丢弃
+    pub(crate) fn attach(&self, role: WsRole, sink: FrameSink, max_size: usize) -> WsFrameHandler {
+        if role.has_event() {
+            let conns = self.0.event_conns.get().unwrap_or(0);
+            self.0.event_conns.set(Some(conns + 1));
+        }
+        let caller = role.has_api().then(|| WsCaller::new(sink));
+        if let Some(caller) = &caller
+            && let Some(old) = self.0.caller.replace(Some(caller.clone()))
+        {
+            old.close();
+        }
+        WsFrameHandler {
+            conn: self.clone(),
+            caller,
+            events: role.has_event(),
+            fragments: Rc::default(),
+            max_size,
+        }
+    }
+
+    fn push_event(&self, event: Event) {
+        let mut events = self.0.events.borrow_mut();
+        if events.len() >= EVENT_BUFFER {
+            tracing::warn!(
+                self_id = self.0.self_id,
+                "event buffer is full, dropping event"
+            );
+            return;
+        }
+        events.push_back(event);
+        self.0.waker.wake();
+    }
+
+    pub(crate) fn detach(&self, handler: &WsFrameHandler) {
+        if handler.events {
+            let conns = self.0.event_conns.get().unwrap_or(1) - 1;
+            self.0.event_conns.set(Some(conns));
+            if conns == 0 {
+                self.0.waker.wake();
+            }
+        }
+        let Some(caller) = &handler.caller else {
+            return;
+        };
+        caller.close();
+        let mut current = self.0.caller.borrow_mut();
+        if current.as_ref().is_some_and(|c| c.same(caller)) {
+            *current = None;
+        }
+    }
+}
+
+/// 正在接收的分片消息，超过上限后丢弃剩余的分片
+#[derive(Debug, Default)]
+struct Fragments {
+    buf: Vec<u8>,
+    overflow: bool,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct WsFrameHandler {
+    conn: WsConn,
+    caller: Option<WsCaller>,
+    events: bool,
+    fragments: Rc<RefCell<Fragments>>,
+    max_size: usize,
+}
+
+impl WsFrameHandler {
+    /// 缓存分片，收到最后一片时返回完整的消息
+    ///
+    /// ntex 的 [`Codec`] 不拼接分片，go-cqhttp 等实现会把较大的消息分片发送
+    fn reassemble(&self, item: Item) -> Option<Bytes> {
+        let mut fragments = self.fragments.borrow_mut();
+        let (data, last) = match item {
+            Item::FirstText(data) | Item::FirstBinary(data) => {
+                *fragments = Fragments::default();
+                (data, false)
+            }
+            Item::Continue(data) => (data, false),
+            Item::Last(data) => (data, true),
+        };
+        if !fragments.overflow {
+            if fragments.buf.len() + data.len() > self.max_size {
+                tracing::warn!(
+                    max_size = self.max_size,
+                    "dropping oversized fragmented message"
+                );
+                *fragments = Fragments {
+                    buf: Vec::new(),
+                    overflow: true,
+                };
+            } else {
+                fragments.buf.extend_from_slice(&data);
+            }
+        }
+        if !last {
+            return None;
+        }
+        let Fragments { buf, overflow } = std::mem::take(&mut *fragments);
+        (!overflow).then(|| Bytes::from(buf))
+    }
+
+    pub(crate) fn handle(&self, frame: Frame) -> Option<Message> {
+        let payload = match frame {
+            Frame::Text(payload) | Frame::Binary(payload) => payload,
+            Frame::Continuation(item) => self.reassemble(item)?,
+            Frame::Ping(payload) => return Some(Message::Pong(payload)),
+            Frame::Close(reason) => return Some(Message::Close(reason)),
+            Frame::Pong(_) => return None,
+        };
+        let value = match serde_json::from_slice::<Value>(&payload) {
+            Ok(value) => value,
+            Err(e) => {
+                tracing::warn!(error = %e, "invalid json frame");
+                return None;
+            }
+        };
+        let value = match &self.caller {
+            Some(caller) => caller.dispatch(value)?,
+            None => value,
+        };
+        if !self.events {
+            return None;
+        }
+        match serde_json::from_value::<Event>(value) {
+            Ok(event) => self.conn.push_event(event),
+            Err(e) => tracing::warn!(error = %e, "invalid event"),
+        }
+        None
+    }
+}
@@ -1,14 +1,24 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::{
     adapters::http::{CqReq, Entry, JsonReq},
     models::{
-        basic_type::{GroupRole, Sex},
+        basic_type::{GroupRequestType, GroupRole, Sex, number_or_string},
+        cq::{self, CqError},
         event::Sender,
-        message::{CqMsg, JsonMsgRecv, JsonMsgSend, Recv},
+        message::{CqMsg, Forward, JsonMsgRecv, JsonMsgSend, NodeSend, Recv, Send, Text},
     },
 };
 
+/// `auto_escape` 为 `true` 时整条消息都是纯文本
+fn cq_into_json(message: CqMsg, auto_escape: bool) -> Result<JsonMsgSend, CqError> {
+    if auto_escape {
+        Ok(JsonMsgSend::Segment(Send::Text(Text { text: message.0 })))
+    } else {
+        JsonMsgSend::try_from(message)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct SendMessageResponse {
     pub message_id: i32,
@@ -25,6 +35,7 @@ impl Entry for SendMessageRequestCq {
     type Output = SendMessageResponse;
 }
 impl CqReq for SendMessageRequestCq {}
+impl JsonReq for SendMessageRequestCq {}
 
 #[derive(Debug, Serialize)]
 pub struct SendMessageRequestJson {
@@ -38,6 +49,30 @@ impl Entry for SendMessageRequestJson {
 }
 impl JsonReq for SendMessageRequestJson {}
 
+impl TryFrom<SendMessageRequestJson> for SendMessageRequestCq {
+    type Error = CqError;
+
+    fn try_from(value: SendMessageRequestJson) -> Result<Self, Self::Error> {
+        Ok(Self {
+            user_id: value.user_id,
+            message: CqMsg::try_from(&value.message)?,
+            auto_escape: false,
+        })
+    }
+}
+
+impl TryFrom<SendMessageRequestCq> for SendMessageRequestJson {
+    type Error = CqError;
+
+    fn try_from(value: SendMessageRequestCq) -> Result<Self, Self::Error> {
+        Ok(Self {
+            user_id: value.user_id,
+            message: cq_into_json(value.message, value.auto_escape)?,
+            auto_escape: false,
+        })
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct SendGroupMessageResponse {
     pub message_id: i32,
@@ -54,6 +89,7 @@ impl Entry for SendGroupMessageRequestCq {
     type Output = SendGroupMessageResponse;
 }
 impl CqReq for SendGroupMessageRequestCq {}
+impl JsonReq for SendGroupMessageRequestCq {}
 
 #[derive(Debug, Serialize)]
 pub struct SendGroupMessageRequestJson {
@@ -67,12 +103,38 @@ impl Entry for SendGroupMessageRequestJson {
 }
 impl JsonReq for SendGroupMessageRequestJson {}
 
+impl TryFrom<SendGroupMessageRequestJson> for SendGroupMessageRequestCq {
+    type Error = CqError;
+
+    fn try_from(value: SendGroupMessageRequestJson) -> Result<Self, Self::Error> {
+        Ok(Self {
+            group_id: value.group_id,
+            message: CqMsg::try_from(&value.message)?,
+            auto_escape: false,
+        })
+    }
+}
+
+impl TryFrom<SendGroupMessageRequestCq> for SendGroupMessageRequestJson {
+    type Error = CqError;
+
+    fn try_from(value: SendGroupMessageRequestCq) -> Result<Self, Self::Error> {
+        Ok(Self {
+            group_id: value.group_id,
+            message: cq_into_json(value.message, value.auto_escape)?,
+            auto_escape: false,
+        })
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct SendMsgResponse {
     pub message_id: i32,
 }
 
-#[derive(Debug, Serialize)]
+/// `send_msg` 的 `message_type`，按 OneBot 序列化为 `"private"`、`"group"`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum MessageType {
     Private,
     Group,
@@ -95,6 +157,7 @@ impl Entry for SendMsgRequestCq {
     type Output = SendMsgResponse;
 }
 impl CqReq for SendMsgRequestCq {}
+impl JsonReq for SendMsgRequestCq {}
 
 #[derive(Debug, Serialize)]
 pub struct SendMsgRequestJson {
@@ -114,9 +177,44 @@ impl Entry for SendMsgRequestJson {
 }
 impl JsonReq for SendMsgRequestJson {}
 
-#[derive(Debug, Deserialize)]
+impl TryFrom<SendMsgRequestJson> for SendMsgRequestCq {
+    type Error = CqError;
+
+    fn try_from(value: SendMsgRequestJson) -> Result<Self, Self::Error> {
+        Ok(Self {
+            message_type: value.message_type,
+            user_id: value.user_id,
+            group_id: value.group_id,
+            message: CqMsg::try_from(&value.message)?,
+            auto_escape: false,
+        })
+    }
+}
+
+impl TryFrom<SendMsgRequestCq> for SendMsgRequestJson {
+    type Error = CqError;
+
+    fn try_from(value: SendMsgRequestCq) -> Result<Self, Self::Error> {
+        Ok(Self {
+            message_type: value.message_type,
+            user_id: value.user_id,
+            group_id: value.group_id,
+            message: cq_into_json(value.message, value.auto_escape)?,
+            auto_escape: false,
+        })
+    }
+}
+
+/// 没有返回数据的动作，`data` 为 `null` 或任意内容都接受
+#[derive(Debug)]
 pub struct Empty {}
 
+impl<'de> Deserialize<'de> for Empty {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        serde::de::IgnoredAny::deserialize(deserializer).map(|_| Empty {})
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct DeleteMessageRequest {
     pub message_id: i32,
@@ -162,7 +260,83 @@ impl JsonReq for GetForwardMessageRequest {}
 
 #[derive(Debug, Deserialize)]
 pub struct GetForwardMessageResponse {
-    pub messages: Vec<Recv>,
+    pub messages: Vec<ForwardNode>,
+}
+
+/// 合并转发中的一条消息
+#[derive(Debug, Clone, Deserialize)]
+pub struct ForwardNode {
+    pub sender: ForwardSender,
+    pub time: i64,
+    /// go-cqhttp 为 `content`，NapCat 等为 `message`，CQ 码字符串会被解析
+    #[serde(alias = "message", deserialize_with = "cq::json_or_cq")]
+    pub content: JsonMsgRecv,
+}
+
+impl ForwardNode {
+    /// `content` 中嵌套的合并转发
+    pub fn forwards(&self) -> impl Iterator<Item = &Forward> {
+        self.content
+            .segments()
+            .iter()
+            .filter_map(|segment| match segment {
+                Recv::Forward(forward) => Some(forward),
+                _ => None,
+            })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ForwardSender {
+    #[serde(deserialize_with = "number_or_string::deserialize")]
+    pub user_id: i64,
+    #[serde(default)]
+    pub nickname: String,
+}
+
+/// 节点按 `{"type": "node", "data": {..}}` 发送
+fn serialize_nodes<S: Serializer>(nodes: &[NodeSend], serializer: S) -> Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct Node<'a> {
+        r#type: &'static str,
+        data: &'a NodeSend,
+    }
+    serializer.collect_seq(nodes.iter().map(|data| Node {
+        r#type: "node",
+        data,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct SendGroupForwardMessageRequest {
+    pub group_id: i64,
+    #[serde(serialize_with = "serialize_nodes")]
+    pub messages: Vec<NodeSend>,
+}
+impl Entry for SendGroupForwardMessageRequest {
+    const ENTRY: &'static str = "send_group_forward_msg";
+    type Output = SendForwardMessageResponse;
+}
+impl JsonReq for SendGroupForwardMessageRequest {}
+
+#[derive(Debug, Serialize)]
+pub struct SendPrivateForwardMessageRequest {
+    pub user_id: i64,
+    #[serde(serialize_with = "serialize_nodes")]
+    pub messages: Vec<NodeSend>,
+}
+impl Entry for SendPrivateForwardMessageRequest {
+    const ENTRY: &'static str = "send_private_forward_msg";
+    type Output = SendForwardMessageResponse;
+}
+impl JsonReq for SendPrivateForwardMessageRequest {}
+
+#[derive(Debug, Deserialize)]
+pub struct SendForwardMessageResponse {
+    pub message_id: i32,
+    /// 合并转发的 ID，可用于 [`Forward`](crate::models::message::Forward) 段
+    #[serde(default)]
+    pub forward_id: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -362,7 +536,7 @@ impl JsonReq for SetFriendAddRequest {}
 pub struct SetGroupAddRequest {
     pub flag: String,
     #[serde(rename = "type")]
-    pub sub_type: String,
+    pub sub_type: GroupRequestType,
     #[serde(default)]
     pub approve: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -535,7 +709,7 @@ pub struct GroupHonorInfo {
     pub emotion_list: Option<Vec<HonorInfo>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum HonorType {
     Talkative,
@@ -668,7 +842,7 @@ impl Entry for CanSendRecordRequest {
 impl CqReq for CanSendRecordRequest {}
 impl JsonReq for CanSendRecordRequest {}
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StatusResponse {
     pub online: Option<bool>,
     pub good: Option<bool>,
@@ -719,3 +893,15 @@ impl Entry for CleanCacheRequest {
 }
 impl CqReq for CleanCacheRequest {}
 impl JsonReq for CleanCacheRequest {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_type_uses_onebot_names() {
+        let names =
+            [MessageType::Private, MessageType::Group].map(|t| serde_json::to_value(t).unwrap());
+        assert_eq!(names, ["private", "group"]);
+    }
+}
@@ -0,0 +1,520 @@
+use std::borrow::Cow;
+
+use ntex::http::{Client, Uri};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    adapters::{
+        auth::ForwardMethod,
+        caller::{ApiCaller, HttpCaller},
+        http::{ActionResult, Entry, JsonReq, Mode, ModePolicy, RawAction},
+    },
+    api::*,
+    error::Error,
+    models::{
+        basic_type::GroupRequestType,
+        message::{JsonMsgSend, NodeSend},
+    },
+};
+
+/// 一个账号的调用句柄，持有传输方式与选择 [`Mode`] 的 [`ModePolicy`]
+///
+/// 每个 [`crate::api`] 中的动作都有同名方法，没有返回数据的动作返回 `()`。
+/// 发送与管理类动作可能被实现异步执行，返回 [`ActionResult`]；查询类动作需要数据，直接返回结果。
+///
+/// 群发与回复需要不同模式时，可以克隆出两个句柄：
+/// `let broadcast = bot.clone().with_mode(Mode::RateLimited);`，
+/// 也可以对单次发送使用 `send_*_with` 指定模式，不查 [`ModePolicy`]
+#[derive(Debug, Clone)]
+pub struct Bot<C = HttpCaller> {
+    caller: C,
+    modes: ModePolicy,
+}
+
+impl Bot<HttpCaller> {
+    pub fn http(client: Client, base: Uri, auth: ForwardMethod) -> Self {
+        Self::new(HttpCaller { client, base, auth })
+    }
+}
+
+impl<C: ApiCaller> Bot<C> {
+    pub fn new(caller: C) -> Self {
+        Self {
+            caller,
+            modes: ModePolicy::default(),
+        }
+    }
+
+    /// 所有动作默认使用 `mode`，已按类别或名称配置的不受影响
+    ///
+    /// 需要数据的调用（[`Bot::call`]、[`Bot::call_raw`] 与查询类方法）始终使用 [`Mode::Default`]
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.modes.default = mode;
+        self
+    }
+
+    pub fn with_modes(mut self, modes: ModePolicy) -> Self {
+        self.modes = modes;
+        self
+    }
+
+    pub fn caller(&self) -> &C {
+        &self.caller
+    }
+
+    pub fn modes(&self) -> &ModePolicy {
+        &self.modes
+    }
+
+    /// 按 [`ModePolicy`] 选择模式调用任意请求
+    pub async fn submit<T: JsonReq>(&self, data: T) -> Result<ActionResult<T::Output>, Error> {
+        self.submit_with(data, self.modes.mode_for(T::ENTRY)).await
+    }
+
+    /// 本次调用使用 `mode`，不查 [`ModePolicy`]
+    pub async fn submit_with<T: JsonReq>(
+        &self,
+        data: T,
+        mode: Mode,
+    ) -> Result<ActionResult<T::Output>, Error> {
+        self.caller.invoke(data, mode).await
+    }
+
+    /// 以 [`Mode::Default`] 调用并返回数据，其他模式只会被接受而没有数据
+    pub async fn call<T: JsonReq>(&self, data: T) -> Result<T::Output, Error> {
+        Ok(self.submit_with(data, Mode::Default).await?.into_ok()?)
+    }
+
+    async fn exec<T>(&self, data: T) -> Result<ActionResult<()>, Error>
+    where
+        T: JsonReq + Entry<Output = Empty>,
+    {
+        self.submit(data).await.map(|result| result.map(drop))
+    }
+
+    /// 按名称调用任意动作，返回 `data`，与 [`Bot::call`] 相同使用 [`Mode::Default`]
+    pub async fn call_raw<P: Serialize>(
+        &self,
+        action: impl Into<Cow<'static, str>>,
+        params: P,
+    ) -> Result<Value, Error> {
+        Ok(self
+            .caller
+            .call_raw(RawAction::new(action, params), Mode::Default)
+            .await?
+            .into_result()?
+            .into_ok()?)
+    }
+
+    pub async fn send_private_msg(
+        &self,
+        user_id: i64,
+        message: impl Into<JsonMsgSend>,
+    ) -> Result<ActionResult<SendMessageResponse>, Error> {
+        let mode = self.modes.mode_for(SendMessageRequestJson::ENTRY);
+        self.send_private_msg_with(user_id, message, mode).await
+    }
+
+    pub async fn send_private_msg_with(
+        &self,
+        user_id: i64,
+        message: impl Into<JsonMsgSend>,
+        mode: Mode,
+    ) -> Result<ActionResult<SendMessageResponse>, Error> {
+        self.submit_with(
+            SendMessageRequestJson {
+                user_id,
+                message: message.into(),
+                auto_escape: false,
+            },
+            mode,
+        )
+        .await
+    }
+
+    pub async fn send_group_msg(
+        &self,
+        group_id: i64,
+        message: impl Into<JsonMsgSend>,
+    ) -> Result<ActionResult<SendGroupMessageResponse>, Error> {
+        let mode = self.modes.mode_for(SendGroupMessageRequestJson::ENTRY);
+        self.send_group_msg_with(group_id, message, mode).await
+    }
+
+    pub async fn send_group_msg_with(
+        &self,
+        group_id: i64,
+        message: impl Into<JsonMsgSend>,
+        mode: Mode,
+    ) -> Result<ActionResult<SendGroupMessageResponse>, Error> {
+        self.submit_with(
+            SendGroupMessageRequestJson {
+                group_id,
+                message: message.into(),
+                auto_escape: false,
+            },
+            mode,
+        )
+        .await
+    }
+
+    /// `id` 为 `message_type` 对应的 `user_id` 或 `group_id`
+    pub async fn send_msg(
+        &self,
+        message_type: MessageType,
+        id: i64,
+        message: impl Into<JsonMsgSend>,
+    ) -> Result<ActionResult<SendMsgResponse>, Error> {
+        let mode = self.modes.mode_for(SendMsgRequestJson::ENTRY);
+        self.send_msg_with(message_type, id, message, mode).await
+    }
+
+    pub async fn send_msg_with(
+        &self,
+        message_type: MessageType,
+        id: i64,
+        message: impl Into<JsonMsgSend>,
+        mode: Mode,
+    ) -> Result<ActionResult<SendMsgResponse>, Error> {
+        let (user_id, group_id) = match message_type {
+            MessageType::Private => (Some(id), None),
+            MessageType::Group => (None, Some(id)),
+        };
+        self.submit_with(
+            SendMsgRequestJson {
+                message_type: Some(message_type),
+                user_id,
+                group_id,
+                message: message.into(),
+                auto_escape: false,
+            },
+            mode,
+        )
+        .await
+    }
+
+    pub async fn delete_msg(&self, message_id: i32) -> Result<ActionResult<()>, Error> {
+        self.exec(DeleteMessageRequest { message_id }).await
+    }
+
+    pub async fn get_msg(&self, message_id: i32) -> Result<GetMessageResponse, Error> {
+        self.call(GetMessageRequest { message_id }).await
+    }
+
+    pub async fn get_forward_msg(
+        &self,
+        id: impl Into<String>,
+    ) -> Result<GetForwardMessageResponse, Error> {
+        self.call(GetForwardMessageRequest { id: id.into() }).await
+    }
+
+    pub async fn send_group_forward_msg(
+        &self,
+        group_id: i64,
+        messages: Vec<NodeSend>,
+    ) -> Result<ActionResult<SendForwardMessageResponse>, Error> {
+        let mode = self.modes.mode_for(SendGroupForwardMessageRequest::ENTRY);
+        self.send_group_forward_msg_with(group_id, messages, mode)
+            .await
+    }
+
+    pub async fn send_group_forward_msg_with(
+        &self,
+        group_id: i64,
+        messages: Vec<NodeSend>,
+        mode: Mode,
+    ) -> Result<ActionResult<SendForwardMessageResponse>, Error> {
+        self.submit_with(SendGroupForwardMessageRequest { group_id, messages }, mode)
+            .await
+    }
+
+    pub async fn send_private_forward_msg(
+        &self,
+        user_id: i64,
+        messages: Vec<NodeSend>,
+    ) -> Result<ActionResult<SendForwardMessageResponse>, Error> {
+        let mode = self.modes.mode_for(SendPrivateForwardMessageRequest::ENTRY);
+        self.send_private_forward_msg_with(user_id, messages, mode)
+            .await
+    }
+
+    pub async fn send_private_forward_msg_with(
+        &self,
+        user_id: i64,
+        messages: Vec<NodeSend>,
+        mode: Mode,
+    ) -> Result<ActionResult<SendForwardMessageResponse>, Error> {
+        self.submit_with(SendPrivateForwardMessageRequest { user_id, messages }, mode)
+            .await
+    }
+
+    pub async fn send_like(&self, user_id: i64, times: i32) -> Result<ActionResult<()>, Error> {
+        self.exec(SendLikeRequest { user_id, times }).await
+    }
+
+    pub async fn set_group_kick(
+        &self,
+        group_id: i64,
+        user_id: i64,
+        reject_add_request: bool,
+    ) -> Result<ActionResult<()>, Error> {
+        self.exec(SetGroupKickRequest {
+            group_id,
+            user_id,
+            reject_add_request,
+        })
+        .await
+    }
+
+    /// `duration` 单位为秒，0 为解除禁言
+    pub async fn set_group_ban(
+        &self,
+        group_id: i64,
+        user_id: i64,
+        duration: i32,
+    ) -> Result<ActionResult<()>, Error> {
+        self.exec(SetGroupBanRequest {
+            group_id,
+            user_id,
+            duration,
+        })
+        .await
+    }
+
+    /// `flag` 为匿名消息中的 `anonymous.flag`
+    pub async fn set_group_anonymous_ban(
+        &self,
+        group_id: i64,
+        flag: impl Into<String>,
+        duration: i32,
+    ) -> Result<ActionResult<()>, Error> {
+        self.exec(SetGroupAnonymousBanRequest {
+            group_id,
+            anonymous: None,
+            anonymous_flag: Some(flag.into()),
+            flag: None,
+            duration,
+        })
+        .await
+    }
+
+    pub async fn set_group_whole_ban(
+        &self,
+        group_id: i64,
+        enable: bool,
+    ) -> Result<ActionResult<()>, Error> {
+        self.exec(SetGroupWholeBanRequest { group_id, enable })
+            .await
+    }
+
+    pub async fn set_group_admin(
+        &self,
+        group_id: i64,
+        user_id: i64,
+        enable: bool,
+    ) -> Result<ActionResult<()>, Error> {
+        self.exec(SetGroupAdminRequest {
+            group_id,
+            user_id,
+            enable,
+        })
+        .await
+    }
+
+    pub async fn set_group_card(
+        &self,
+        group_id: i64,
+        user_id: i64,
+        card: impl Into<String>,
+    ) -> Result<ActionResult<()>, Error> {
+        self.exec(SetGroupCardRequest {
+            group_id,
+            user_id,
+            card: card.into(),
+        })
+        .await
+    }
+
+    pub async fn set_group_name(
+        &self,
+        group_id: i64,
+        group_name: impl Into<String>,
+    ) -> Result<ActionResult<()>, Error> {
+        self.exec(SetGroupNameRequest {
+            group_id,
+            group_name: group_name.into(),
+        })
+        .await
+    }
+
+    pub async fn set_group_leave(
+        &self,
+        group_id: i64,
+        is_dismiss: bool,
+    ) -> Result<ActionResult<()>, Error> {
+        self.exec(SetGroupLeaveRequest {
+            group_id,
+            is_dismiss,
+        })
+        .await
+    }
+
+    /// `duration` 单位为秒，-1 为永久
+    pub async fn set_group_special_title(
+        &self,
+        group_id: i64,
+        user_id: i64,
+        special_title: impl Into<String>,
+        duration: i32,
+    ) -> Result<ActionResult<()>, Error> {
+        self.exec(SetGroupSpecialTitleRequest {
+            group_id,
+            user_id,
+            special_title: special_title.into(),
+            duration,
+        })
+        .await
+    }
+
+    pub async fn set_friend_add_request(
+        &self,
+        flag: impl Into<String>,
+        approve: bool,
+        remark: Option<String>,
+    ) -> Result<ActionResult<()>, Error> {
+        self.exec(SetFriendAddRequest {
+            flag: flag.into(),
+            approve,
+            remark,
+        })
+        .await
+    }
+
+    pub async fn set_group_add_request(
+        &self,
+        flag: impl Into<String>,
+        sub_type: GroupRequestType,
+        approve: bool,
+        reason: Option<String>,
+    ) -> Result<ActionResult<()>, Error> {
+        self.exec(SetGroupAddRequest {
+            flag: flag.into(),
+            sub_type,
+            approve,
+            reason,
+        })
+        .await
+    }
+
+    pub async fn get_login_info(&self) -> Result<LoginInfo, Error> {
+        self.call(GetLoginInfoRequest {}).await
+    }
+
+    pub async fn get_stranger_info(
+        &self,
+        user_id: i64,
+        no_cache: bool,
+    ) -> Result<StrangerInfo, Error> {
+        self.call(GetStrangerInfoRequest { user_id, no_cache })
+            .await
+    }
+
+    pub async fn get_friend_list(&self) -> Result<Vec<Friend>, Error> {
+        self.call(GetFriendListRequest {}).await
+    }
+
+    pub async fn get_group_info(&self, group_id: i64, no_cache: bool) -> Result<GroupInfo, Error> {
+        self.call(GetGroupInfoRequest { group_id, no_cache }).await
+    }
+
+    pub async fn get_group_list(&self) -> Result<Vec<GroupInfo>, Error> {
+        self.call(GetGroupListRequest {}).await
+    }
+
+    pub async fn get_group_member_info(
+        &self,
+        group_id: i64,
+        user_id: i64,
+        no_cache: bool,
+    ) -> Result<GroupMemberInfo, Error> {
+        self.call(GetGroupMemberInfoRequest {
+            group_id,
+            user_id,
+            no_cache,
+        })
+        .await
+    }
+
+    pub async fn get_group_member_list(
+        &self,
+        group_id: i64,
+    ) -> Result<Vec<GroupMemberInfo>, Error> {
+        self.call(GetGroupMemberListRequest { group_id }).await
+    }
+
+    pub async fn get_group_honor_info(
+        &self,
+        group_id: i64,
+        r#type: HonorType,
+    ) -> Result<GroupHonorInfo, Error> {
+        self.call(GetGroupHonorInfoRequest { group_id, r#type })
+            .await
+    }
+
+    pub async fn get_cookies(&self, domain: Option<String>) -> Result<CookiesResponse, Error> {
+        self.call(GetCookiesRequest { domain }).await
+    }
+
+    pub async fn get_csrf_token(&self) -> Result<CsrfTokenResponse, Error> {
+        self.call(GetCsrfTokenRequest {}).await
+    }
+
+    pub async fn get_credentials(
+        &self,
+        domain: Option<String>,
+    ) -> Result<CredentialsResponse, Error> {
+        self.call(GetCredentialsRequest { domain }).await
+    }
+
+    pub async fn get_record(
+        &self,
+        file: impl Into<String>,
+        out_format: impl Into<String>,
+    ) -> Result<RecordResponse, Error> {
+        self.call(GetRecordRequest {
+            file: file.into(),
+            out_format: out_format.into(),
+        })
+        .await
+    }
+
+    pub async fn get_image(&self, file: impl Into<String>) -> Result<ImageResponse, Error> {
+        self.call(GetImageRequest { file: file.into() }).await
+    }
+
+    pub async fn can_send_image(&self) -> Result<bool, Error> {
+        self.call(CanSendImageRequest {}).await.map(|r| r.yes)
+    }
+
+    pub async fn can_send_record(&self) -> Result<bool, Error> {
+        self.call(CanSendRecordRequest {}).await.map(|r| r.yes)
+    }
+
+    pub async fn get_status(&self) -> Result<StatusResponse, Error> {
+        self.call(GetStatusRequest {}).await
+    }
+
+    pub async fn get_version_info(&self) -> Result<VersionInfo, Error> {
+        self.call(GetVersionInfoRequest {}).await
+    }
+
+    /// `delay` 单位为毫秒
+    pub async fn set_restart(&self, delay: i32) -> Result<ActionResult<()>, Error> {
+        self.exec(SetRestartRequest { delay }).await
+    }
+
+    pub async fn clean_cache(&self) -> Result<ActionResult<()>, Error> {
+        self.exec(CleanCacheRequest {}).await
+    }
+}
@@ -1,7 +1,322 @@
-use ntex::http::uri::{InvalidUri, InvalidUriParts};
+use std::{
+    borrow::Cow,
+    fmt::{self, Display},
+    io,
+    time::Duration,
+};
+
+use ntex::http::{
+    client::error::JsonPayloadError,
+    error::PayloadError,
+    uri::{InvalidUri, InvalidUriParts},
+};
+use serde_json::Value;
+
+use crate::{
+    adapters::{
+        auth::ForwardToBackwardError,
+        forward_ws::ForwardWsError,
+        http::{Accepted, ActionError, ExecError, SendError, SendErrorKind},
+        http_post::SignatureError,
+        ws::WsExecError,
+    },
+    models::{cq::CqError, resend::ResendError},
+};
 
 #[derive(Debug)]
 pub enum WithPathAndQueryError {
     IntoPart(InvalidUri),
     IntoUri(InvalidUriParts),
 }
+
+impl Display for WithPathAndQueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WithPathAndQueryError::IntoPart(_) => f.write_str("invalid path and query"),
+            WithPathAndQueryError::IntoUri(_) => f.write_str("invalid uri parts"),
+        }
+    }
+}
+
+impl std::error::Error for WithPathAndQueryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WithPathAndQueryError::IntoPart(e) => Some(e),
+            WithPathAndQueryError::IntoUri(e) => Some(e),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    Http,
+    WebSocket,
+}
+
+impl Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Transport::Http => "http",
+            Transport::WebSocket => "websocket",
+        })
+    }
+}
+
+/// 错误的大类，便于统一处理
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    /// 请求参数或地址无法编码
+    Encode,
+    /// 连接或发送失败
+    Transport,
+    /// 超过时限没有返回
+    Timeout,
+    /// 返回无法解析
+    Decode,
+    /// 实现返回失败的 `retcode`
+    Action,
+    /// 需要数据的动作只被接受，没有执行结果
+    Accepted,
+    /// 消息格式、鉴权配置、签名等
+    Other,
+}
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    Http(ExecError),
+    Ws(WsExecError),
+    ForwardWs(ForwardWsError),
+    Action(ActionError<Value>),
+    /// 需要数据的动作返回了 `retcode` 1
+    Accepted(Accepted),
+    /// 排队中的任务在完成前被丢弃
+    Canceled,
+    /// 超过时限没有返回，请求已被取消
+    ///
+    /// `None` 为 HTTP 客户端自身的请求或读取超时
+    Timeout(Option<Duration>),
+    Cq(CqError),
+    Resend(ResendError),
+    Signature(SignatureError),
+    ForwardToBackward(ForwardToBackwardError),
+    WithPathAndQuery(WithPathAndQueryError),
+}
+
+/// crate 内所有错误的汇总，记录出错的动作与传输方式
+///
+/// 是 `Send + Sync` 的，可以用 `?` 转为 `anyhow::Error` 等
+#[derive(Debug)]
+pub struct Error {
+    pub kind: ErrorKind,
+    /// 出错的 [`Entry::ENTRY`](crate::adapters::http::Entry::ENTRY)
+    pub action: Option<Cow<'static, str>>,
+    pub transport: Option<Transport>,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind) -> Self {
+        Self {
+            kind,
+            action: None,
+            transport: None,
+        }
+    }
+
+    pub fn with_action(mut self, action: impl Into<Cow<'static, str>>) -> Self {
+        self.action = Some(action.into());
+        self
+    }
+
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// 尚未记录传输方式时补上
+    pub(crate) fn or_transport(mut self, transport: Option<Transport>) -> Self {
+        self.transport = self.transport.or(transport);
+        self
+    }
+
+    pub fn category(&self) -> Category {
+        match &self.kind {
+            ErrorKind::Http(e) => match e {
+                ExecError::EncodePathAndQuery(_)
+                | ExecError::EncodeUrl(_)
+                | ExecError::EncodeQueryData(_) => Category::Encode,
+                ExecError::Send(_) => Category::Transport,
+                ExecError::Decode(_) => Category::Decode,
+            },
+            ErrorKind::Ws(e) => match e {
+                WsExecError::Encode(_) => Category::Encode,
+                WsExecError::Send(_) | WsExecError::NoApiConnection | WsExecError::Closed => {
+                    Category::Transport
+                }
+                WsExecError::Decode(_) => Category::Decode,
+            },
+            ErrorKind::ForwardWs(e) => match e {
+                ForwardWsError::EncodePathAndQuery(_) | ForwardWsError::EncodeUrl(_) => {
+                    Category::Encode
+                }
+                ForwardWsError::Build(_) | ForwardWsError::Connect(_) => Category::Transport,
+            },
+            ErrorKind::Action(_) => Category::Action,
+            ErrorKind::Accepted(_) => Category::Accepted,
+            ErrorKind::Timeout(_) => Category::Timeout,
+            ErrorKind::Canceled
+            | ErrorKind::Cq(_)
+            | ErrorKind::Resend(_)
+            | ErrorKind::Signature(_)
+            | ErrorKind::ForwardToBackward(_)
+            | ErrorKind::WithPathAndQuery(_) => Category::Other,
+        }
+    }
+
+    /// 实现返回的失败
+    pub fn action_error(&self) -> Option<&ActionError<Value>> {
+        match &self.kind {
+            ErrorKind::Action(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // 实现返回的失败本身已带有动作名
+        if !matches!(self.kind, ErrorKind::Action(_)) {
+            if let Some(action) = &self.action {
+                write!(f, "`{action}` ")?;
+            }
+            if let Some(transport) = self.transport {
+                write!(f, "over {transport} ")?;
+            }
+            if self.action.is_some() || self.transport.is_some() {
+                f.write_str("failed: ")?;
+            }
+        }
+        match &self.kind {
+            ErrorKind::Http(e) => e.fmt(f),
+            ErrorKind::Ws(e) => e.fmt(f),
+            ErrorKind::ForwardWs(e) => e.fmt(f),
+            ErrorKind::Action(e) => e.fmt(f),
+            ErrorKind::Accepted(accepted) => write!(
+                f,
+                "action was accepted with {:?} mode and returned no data",
+                accepted.mode
+            ),
+            ErrorKind::Canceled => f.write_str("task was dropped before completion"),
+            ErrorKind::Timeout(Some(limit)) => write!(f, "timed out after {limit:?}"),
+            ErrorKind::Timeout(None) => f.write_str("timed out"),
+            ErrorKind::Cq(e) => e.fmt(f),
+            ErrorKind::Resend(e) => e.fmt(f),
+            ErrorKind::Signature(e) => e.fmt(f),
+            ErrorKind::ForwardToBackward(e) => e.fmt(f),
+            ErrorKind::WithPathAndQuery(e) => e.fmt(f),
+        }
+    }
+}
+
+/// `Display` 已包含被包装错误的信息，`source` 跳过它，直接为它的 `source`，
+/// 避免 `{:#}` 等逐级打印时重复
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ErrorKind::Http(e) => e.source(),
+            ErrorKind::Ws(e) => e.source(),
+            ErrorKind::ForwardWs(e) => e.source(),
+            ErrorKind::Action(e) => e.source(),
+            ErrorKind::Accepted(_) | ErrorKind::Canceled | ErrorKind::Timeout(_) => None,
+            ErrorKind::Cq(e) => e.source(),
+            ErrorKind::Resend(e) => e.source(),
+            ErrorKind::Signature(e) => e.source(),
+            ErrorKind::ForwardToBackward(e) => e.source(),
+            ErrorKind::WithPathAndQuery(e) => e.source(),
+        }
+    }
+}
+
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Error>();
+};
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Self::new(kind)
+    }
+}
+
+impl From<ExecError> for Error {
+    fn from(e: ExecError) -> Self {
+        let kind = match e {
+            ExecError::Send(SendError {
+                kind: SendErrorKind::Timeout,
+                ..
+            }) => ErrorKind::Timeout(None),
+            ExecError::Decode(JsonPayloadError::Payload(PayloadError::Incomplete(Some(e))))
+                if e.kind() == io::ErrorKind::TimedOut =>
+            {
+                ErrorKind::Timeout(None)
+            }
+            e => ErrorKind::Http(e),
+        };
+        Self::new(kind).with_transport(Transport::Http)
+    }
+}
+
+impl From<WsExecError> for Error {
+    fn from(e: WsExecError) -> Self {
+        Self::new(ErrorKind::Ws(e)).with_transport(Transport::WebSocket)
+    }
+}
+
+impl From<ForwardWsError> for Error {
+    fn from(e: ForwardWsError) -> Self {
+        Self::new(ErrorKind::ForwardWs(e)).with_transport(Transport::WebSocket)
+    }
+}
+
+impl From<ActionError<Value>> for Error {
+    fn from(e: ActionError<Value>) -> Self {
+        let action = e.action.clone();
+        Self::new(ErrorKind::Action(e)).with_action(action)
+    }
+}
+
+impl From<Accepted> for Error {
+    fn from(accepted: Accepted) -> Self {
+        let action = accepted.action.clone();
+        Self::new(ErrorKind::Accepted(accepted)).with_action(action)
+    }
+}
+
+impl From<CqError> for Error {
+    fn from(e: CqError) -> Self {
+        Self::new(ErrorKind::Cq(e))
+    }
+}
+
+impl From<ResendError> for Error {
+    fn from(e: ResendError) -> Self {
+        Self::new(ErrorKind::Resend(e))
+    }
+}
+
+impl From<SignatureError> for Error {
+    fn from(e: SignatureError) -> Self {
+        Self::new(ErrorKind::Signature(e))
+    }
+}
+
+impl From<ForwardToBackwardError> for Error {
+    fn from(e: ForwardToBackwardError) -> Self {
+        Self::new(ErrorKind::ForwardToBackward(e))
+    }
+}
+
+impl From<WithPathAndQueryError> for Error {
+    fn from(e: WithPathAndQueryError) -> Self {
+        Self::new(ErrorKind::WithPathAndQuery(e))
+    }
+}
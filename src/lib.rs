@@ -2,7 +2,9 @@ use utils::PipeOps;
 
 pub mod adapters;
 pub mod api;
+pub mod bot;
 pub mod error;
+pub use error::Error;
 pub mod models;
 
 pub mod utils;
@@ -43,27 +43,104 @@ impl Serialize for OneBotBool {
     }
 }
 
+struct OneBotBoolVisitor;
+
+impl serde::de::Visitor<'_> for OneBotBoolVisitor {
+    type Value = OneBotBool;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("0, 1, a bool or their string form")
+    }
+
+    fn visit_bool<E: serde::de::Error>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(v.into())
+    }
+
+    fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        match v {
+            0 => Ok(OneBotBool::False),
+            1 => Ok(OneBotBool::True),
+            _ => Err(E::custom(format!("invalid value: {v}, need 0 or 1"))),
+        }
+    }
+
+    fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        u64::try_from(v)
+            .map_err(|_| E::custom(format!("invalid value: {v}, need 0 or 1")))
+            .and_then(|v| self.visit_u64(v))
+    }
+
+    // CQ 码中所有参数都是字符串
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        match v {
+            "0" | "false" => Ok(OneBotBool::False),
+            "1" | "true" => Ok(OneBotBool::True),
+            _ => Err(E::custom(format!("invalid value: {v}, need 0 or 1"))),
+        }
+    }
+}
+
 impl<'de> Deserialize<'de> for OneBotBool {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        let value = u8::deserialize(deserializer)?;
-        match value {
-            0 => Ok(OneBotBool::False),
-            1 => Ok(OneBotBool::True),
-            _ => Err(serde::de::Error::custom(format!(
-                "invalid value: {value}, need 0 or 1",
-            ))),
+        deserializer.deserialize_any(OneBotBoolVisitor)
+    }
+}
+
+/// 同时接受数字与数字字符串，CQ 码中所有参数都是字符串
+pub(crate) mod number_or_string {
+    use std::{fmt::Display, str::FromStr};
+
+    use serde::{Deserialize, Deserializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum NumberOrString<T> {
+        Number(T),
+        String(String),
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de> + FromStr,
+        T::Err: Display,
+    {
+        match NumberOrString::<T>::deserialize(deserializer)? {
+            NumberOrString::Number(n) => Ok(n),
+            NumberOrString::String(s) => s.parse().map_err(serde::de::Error::custom),
+        }
+    }
+
+    pub mod option {
+        use std::{fmt::Display, str::FromStr};
+
+        use serde::{Deserialize, Deserializer};
+
+        pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+        where
+            D: Deserializer<'de>,
+            T: Deserialize<'de> + FromStr,
+            T::Err: Display,
+        {
+            Option::<super::NumberOrString<T>>::deserialize(deserializer)?
+                .map(|value| match value {
+                    super::NumberOrString::Number(n) => Ok(n),
+                    super::NumberOrString::String(s) => s.parse().map_err(serde::de::Error::custom),
+                })
+                .transpose()
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum Sex {
     Male,
     Female,
+    #[default]
     Unknown,
 }
 
@@ -74,3 +151,12 @@ pub enum GroupRole {
     Admin,
     Member,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupRequestType {
+    /// 加群请求
+    Add,
+    /// 邀请登录号入群
+    Invite,
+}
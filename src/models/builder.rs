@@ -0,0 +1,205 @@
+use std::{fmt, path::Path};
+
+use super::{
+    cq::CqError,
+    message::{
+        At, AtQqType, CqMsg, Dice, Face, ImageSend, JsonMsgSend, LocationSend, Music, RecordSend,
+        Reply, Rps, Send, Shake, ShareRecv, Text, VideoSend,
+    },
+};
+
+/// 逐段构造消息，相邻的纯文本会合并
+///
+/// 实现了 [`fmt::Write`]，可以直接 `write!(builder, ..)`，也可以用 [`message!`](crate::message)
+#[derive(Debug, Clone, Default)]
+pub struct MessageBuilder {
+    segments: Vec<Send>,
+}
+
+impl MessageBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn segment(mut self, segment: Send) -> Self {
+        self.push(segment);
+        self
+    }
+
+    pub fn push(&mut self, segment: Send) {
+        match (self.segments.last_mut(), segment) {
+            (Some(Send::Text(last)), Send::Text(text)) => last.text.push_str(&text.text),
+            (_, Send::Text(text)) if text.text.is_empty() => {}
+            (_, segment) => self.segments.push(segment),
+        }
+    }
+
+    pub fn text(self, text: impl Into<String>) -> Self {
+        self.segment(Send::Text(Text { text: text.into() }))
+    }
+
+    pub fn at(self, user_id: i64) -> Self {
+        self.segment(Send::At(At {
+            qq: AtQqType::Single(user_id.to_string()),
+        }))
+    }
+
+    pub fn at_all(self) -> Self {
+        self.segment(Send::At(At { qq: AtQqType::All }))
+    }
+
+    pub fn reply(self, message_id: i32) -> Self {
+        self.segment(Send::Reply(Reply {
+            id: message_id.to_string(),
+        }))
+    }
+
+    pub fn face(self, id: i32) -> Self {
+        self.segment(Send::Face(Face { id: id.to_string() }))
+    }
+
+    /// `file` 可以是文件名、`file://`、`http(s)://` 或 `base64://`
+    pub fn image(self, file: impl Into<String>) -> Self {
+        self.segment(Send::Image(ImageSend {
+            file: file.into(),
+            r#type: None,
+            cache: None,
+            proxy: None,
+            timeout: None,
+        }))
+    }
+
+    /// 本地文件，转为 `file://` URI
+    pub fn image_file(self, path: impl AsRef<Path>) -> Self {
+        self.image(file_uri(path.as_ref()))
+    }
+
+    pub fn image_url(self, url: impl Into<String>) -> Self {
+        self.image(url)
+    }
+
+    pub fn record(self, file: impl Into<String>) -> Self {
+        self.segment(Send::Record(RecordSend {
+            file: file.into(),
+            magic: None,
+            url: None,
+            cache: None,
+            proxy: None,
+            timeout: None,
+        }))
+    }
+
+    pub fn record_file(self, path: impl AsRef<Path>) -> Self {
+        self.record(file_uri(path.as_ref()))
+    }
+
+    pub fn video(self, file: impl Into<String>) -> Self {
+        self.segment(Send::Video(VideoSend {
+            file: file.into(),
+            cache: None,
+            proxy: None,
+            timeout: None,
+        }))
+    }
+
+    pub fn video_file(self, path: impl AsRef<Path>) -> Self {
+        self.video(file_uri(path.as_ref()))
+    }
+
+    pub fn rps(self) -> Self {
+        self.segment(Send::Rps(Rps {}))
+    }
+
+    pub fn dice(self) -> Self {
+        self.segment(Send::Dice(Dice {}))
+    }
+
+    pub fn shake(self) -> Self {
+        self.segment(Send::Shake(Shake {}))
+    }
+
+    pub fn share(self, url: impl Into<String>, title: impl Into<String>) -> Self {
+        self.segment(Send::Share(ShareRecv {
+            url: url.into(),
+            title: title.into(),
+            content: None,
+            image: None,
+        }))
+    }
+
+    pub fn location(self, lat: f64, lon: f64) -> Self {
+        self.segment(Send::Location(LocationSend {
+            lat: lat.to_string(),
+            lon: lon.to_string(),
+            title: None,
+            content: None,
+        }))
+    }
+
+    pub fn music(self, music: Music) -> Self {
+        self.segment(Send::Music(music))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    pub fn build(self) -> JsonMsgSend {
+        JsonMsgSend::Array(self.segments)
+    }
+
+    pub fn build_cq(self) -> Result<CqMsg, CqError> {
+        CqMsg::encode(&self.segments)
+    }
+}
+
+/// 相对路径按当前工作目录补全，实现端与机器人的工作目录往往不同
+fn file_uri(path: &Path) -> String {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let path = path.to_string_lossy().replace('\\', "/");
+    if path.starts_with('/') {
+        format!("file://{path}")
+    } else {
+        format!("file:///{path}")
+    }
+}
+
+impl fmt::Write for MessageBuilder {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push(Send::Text(Text { text: s.into() }));
+        Ok(())
+    }
+}
+
+impl From<MessageBuilder> for JsonMsgSend {
+    fn from(value: MessageBuilder) -> Self {
+        value.build()
+    }
+}
+
+impl TryFrom<MessageBuilder> for CqMsg {
+    type Error = CqError;
+
+    fn try_from(value: MessageBuilder) -> Result<Self, Self::Error> {
+        value.build_cq()
+    }
+}
+
+impl Extend<Send> for MessageBuilder {
+    fn extend<T: IntoIterator<Item = Send>>(&mut self, iter: T) {
+        iter.into_iter().for_each(|segment| self.push(segment));
+    }
+}
+
+/// 以 `format!` 的语法构造一段纯文本消息，返回 [`MessageBuilder`]
+///
+/// ```
+/// let name = "world";
+/// let msg = router_bot::message!("hello, {name}").at(10001).build();
+/// ```
+#[macro_export]
+macro_rules! message {
+    ($($arg:tt)*) => {
+        $crate::models::builder::MessageBuilder::new().text(::std::format!($($arg)*))
+    };
+}
@@ -0,0 +1,383 @@
+use serde::{Deserialize, Deserializer, Serialize, de};
+use serde_json::{Map, Value};
+
+use crate::PipeOps;
+
+use super::message::{CqMsg, JsonMsgRecv, JsonMsgSend, Recv, Send};
+
+#[derive(Debug)]
+pub enum CqError {
+    /// `[CQ:` 没有对应的 `]`
+    Unclosed {
+        position: usize,
+    },
+    EmptyType {
+        position: usize,
+    },
+    /// 参数缺少 `=`
+    InvalidParam {
+        position: usize,
+        param: String,
+    },
+    Encode(serde_json::Error),
+    Decode(serde_json::Error),
+    /// 参数不是字符串、数字或布尔值，无法写成 CQ 码
+    Unrepresentable {
+        r#type: String,
+        key: Option<String>,
+    },
+}
+
+impl std::fmt::Display for CqError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CqError::Unclosed { position } => write!(f, "unclosed CQ code at {position}"),
+            CqError::EmptyType { position } => write!(f, "empty CQ code type at {position}"),
+            CqError::InvalidParam { position, param } => {
+                write!(f, "invalid CQ code param `{param}` at {position}")
+            }
+            CqError::Encode(_) => f.write_str("failed to encode segment"),
+            CqError::Decode(_) => f.write_str("failed to decode segment"),
+            CqError::Unrepresentable { r#type, key: None } => {
+                write!(f, "segment `{}` cannot be written as CQ code", r#type)
+            }
+            CqError::Unrepresentable {
+                r#type,
+                key: Some(key),
+            } => write!(
+                f,
+                "param `{key}` of segment `{}` cannot be written as CQ code",
+                r#type
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CqError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CqError::Encode(e) | CqError::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// 一段 CQ 码，纯文本为 `type = "text"`，参数为 `text`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CqCode {
+    pub r#type: String,
+    pub data: Vec<(String, String)>,
+}
+
+const CQ_START: &str = "[CQ:";
+
+/// 纯文本转义 `&`、`[`、`]`
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('[', "&#91;")
+        .replace(']', "&#93;")
+}
+
+/// 参数转义，比纯文本多一个 `,`
+pub fn escape_param(value: &str) -> String {
+    escape(value).replace(',', "&#44;")
+}
+
+pub fn unescape(text: &str) -> String {
+    text.replace("&#44;", ",")
+        .replace("&#91;", "[")
+        .replace("&#93;", "]")
+        .replace("&amp;", "&")
+}
+
+fn text(text: &str) -> CqCode {
+    CqCode {
+        r#type: "text".into(),
+        data: vec![("text".into(), unescape(text))],
+    }
+}
+
+pub fn parse(input: &str) -> Result<Vec<CqCode>, CqError> {
+    let mut codes = Vec::new();
+    let mut rest = input;
+    while let Some(start) = rest.find(CQ_START) {
+        let position = input.len() - rest.len() + start;
+        if start > 0 {
+            codes.push(text(&rest[..start]));
+        }
+        let body = &rest[start + CQ_START.len()..];
+        let end = body.find(']').ok_or(CqError::Unclosed { position })?;
+        let mut parts = body[..end].split(',');
+        let r#type = parts.next().unwrap_or_default();
+        if r#type.is_empty() {
+            return Err(CqError::EmptyType { position });
+        }
+        let data = parts
+            .map(|param| match param.split_once('=') {
+                Some((key, value)) => Ok((unescape(key), unescape(value))),
+                None => Err(CqError::InvalidParam {
+                    position,
+                    param: param.into(),
+                }),
+            })
+            .collect::<Result<_, _>>()?;
+        codes.push(CqCode {
+            r#type: r#type.into(),
+            data,
+        });
+        rest = &body[end + 1..];
+    }
+    if !rest.is_empty() {
+        codes.push(text(rest));
+    }
+    Ok(codes)
+}
+
+impl std::fmt::Display for CqCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.r#type == "text" {
+            return self
+                .data
+                .iter()
+                .filter(|(key, _)| key == "text")
+                .try_for_each(|(_, value)| f.write_str(&escape(value)));
+        }
+        write!(f, "{}{}", CQ_START, self.r#type)?;
+        for (key, value) in &self.data {
+            write!(f, ",{}={}", escape_param(key), escape_param(value))?;
+        }
+        f.write_str("]")
+    }
+}
+
+impl CqCode {
+    /// 转为 `{"type": .., "data": {..}}`，参数都是字符串
+    pub fn to_value(&self) -> Value {
+        let data = self
+            .data
+            .iter()
+            .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+            .collect::<Map<_, _>>();
+        serde_json::json!({ "type": self.r#type, "data": data })
+    }
+
+    pub fn from_value(value: &Value) -> Result<Self, CqError> {
+        let r#type = value
+            .get("type")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_owned();
+        let unrepresentable = |key: Option<&String>| CqError::Unrepresentable {
+            r#type: r#type.clone(),
+            key: key.cloned(),
+        };
+        let data = match value.get("data") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Object(data)) => data
+                .iter()
+                .filter_map(|(key, value)| {
+                    match value {
+                        Value::Null => return None,
+                        Value::String(s) => Ok(s.clone()),
+                        Value::Number(n) => Ok(n.to_string()),
+                        Value::Bool(b) => Ok(b.to_string()),
+                        Value::Array(_) | Value::Object(_) => Err(unrepresentable(Some(key))),
+                    }
+                    .map(|value| (key.clone(), value))
+                    .pipe(Some)
+                })
+                .collect::<Result<_, _>>()?,
+            Some(_) => return Err(unrepresentable(None)),
+        };
+        Ok(Self { r#type, data })
+    }
+
+    pub fn decode<T>(&self) -> Result<T, CqError>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        serde_json::from_value(self.to_value()).map_err(CqError::Decode)
+    }
+
+    pub fn encode<T: Serialize>(segment: &T) -> Result<Self, CqError> {
+        serde_json::to_value(segment)
+            .map_err(CqError::Encode)?
+            .pipe(|value| Self::from_value(&value))
+    }
+}
+
+impl CqMsg {
+    pub fn parse(&self) -> Result<Vec<CqCode>, CqError> {
+        parse(&self.0)
+    }
+
+    pub fn decode<T>(&self) -> Result<Vec<T>, CqError>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        self.parse()?.iter().map(CqCode::decode).collect()
+    }
+
+    pub fn to_recv(&self) -> Result<Vec<Recv>, CqError> {
+        self.decode()
+    }
+
+    pub fn to_send(&self) -> Result<Vec<Send>, CqError> {
+        self.decode()
+    }
+
+    pub fn encode<'a, T, I>(segments: I) -> Result<Self, CqError>
+    where
+        T: Serialize + 'a,
+        I: IntoIterator<Item = &'a T>,
+    {
+        segments
+            .into_iter()
+            .map(|segment| CqCode::encode(segment).map(|code| code.to_string()))
+            .collect::<Result<String, _>>()
+            .map(CqMsg)
+    }
+}
+
+impl FromIterator<CqCode> for CqMsg {
+    fn from_iter<I: IntoIterator<Item = CqCode>>(iter: I) -> Self {
+        iter.into_iter()
+            .map(|code| code.to_string())
+            .collect::<String>()
+            .pipe(CqMsg)
+    }
+}
+
+/// 相邻的纯文本段会合并为一段
+impl TryFrom<&JsonMsgSend> for CqMsg {
+    type Error = CqError;
+
+    fn try_from(value: &JsonMsgSend) -> Result<Self, Self::Error> {
+        match value {
+            JsonMsgSend::Segment(segment) => CqMsg::encode([segment]),
+            JsonMsgSend::Array(segments) => CqMsg::encode(segments),
+        }
+    }
+}
+
+impl TryFrom<JsonMsgSend> for CqMsg {
+    type Error = CqError;
+
+    fn try_from(value: JsonMsgSend) -> Result<Self, Self::Error> {
+        CqMsg::try_from(&value)
+    }
+}
+
+impl TryFrom<&CqMsg> for JsonMsgSend {
+    type Error = CqError;
+
+    fn try_from(value: &CqMsg) -> Result<Self, Self::Error> {
+        value.to_send().map(JsonMsgSend::Array)
+    }
+}
+
+impl TryFrom<CqMsg> for JsonMsgSend {
+    type Error = CqError;
+
+    fn try_from(value: CqMsg) -> Result<Self, Self::Error> {
+        JsonMsgSend::try_from(&value)
+    }
+}
+
+impl TryFrom<&JsonMsgRecv> for CqMsg {
+    type Error = CqError;
+
+    fn try_from(value: &JsonMsgRecv) -> Result<Self, Self::Error> {
+        match value {
+            JsonMsgRecv::Segment(segment) => CqMsg::encode([segment]),
+            JsonMsgRecv::Array(segments) => CqMsg::encode(segments),
+        }
+    }
+}
+
+impl TryFrom<JsonMsgRecv> for CqMsg {
+    type Error = CqError;
+
+    fn try_from(value: JsonMsgRecv) -> Result<Self, Self::Error> {
+        CqMsg::try_from(&value)
+    }
+}
+
+impl TryFrom<&CqMsg> for JsonMsgRecv {
+    type Error = CqError;
+
+    fn try_from(value: &CqMsg) -> Result<Self, Self::Error> {
+        value.to_recv().map(JsonMsgRecv::Array)
+    }
+}
+
+impl TryFrom<CqMsg> for JsonMsgRecv {
+    type Error = CqError;
+
+    fn try_from(value: CqMsg) -> Result<Self, Self::Error> {
+        JsonMsgRecv::try_from(&value)
+    }
+}
+
+/// 字符串按 CQ 码解析，用于上报格式可能是 `string` 的字段
+pub(crate) fn json_or_cq<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<JsonMsgRecv, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::String(message) => JsonMsgRecv::try_from(CqMsg(message)).map_err(de::Error::custom),
+        message => JsonMsgRecv::deserialize(message).map_err(de::Error::custom),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_round_trip_keeps_escaped_entities() {
+        let text = "a &#91; [b] &amp; c";
+        let code = CqCode {
+            r#type: "text".into(),
+            data: vec![("text".into(), text.into())],
+        };
+        let encoded = code.to_string();
+        assert_eq!(encoded, "a &amp;#91; &#91;b&#93; &amp;amp; c");
+        assert_eq!(parse(&encoded).unwrap(), vec![code]);
+    }
+
+    #[test]
+    fn param_with_comma_round_trip() {
+        let code = CqCode {
+            r#type: "share".into(),
+            data: vec![
+                ("url".into(), "https://example.com/?a=1&b=2".into()),
+                ("title".into(), "a, [b]".into()),
+            ],
+        };
+        let encoded = code.to_string();
+        assert_eq!(
+            encoded,
+            "[CQ:share,url=https://example.com/?a=1&amp;b=2,title=a&#44; &#91;b&#93;]"
+        );
+        assert_eq!(parse(&encoded).unwrap(), vec![code]);
+    }
+
+    #[test]
+    fn parse_splits_text_and_codes() {
+        let codes = parse("hi [CQ:face,id=1]!").unwrap();
+        let types = codes
+            .iter()
+            .map(|code| code.r#type.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(types, ["text", "face", "text"]);
+        assert_eq!(codes[1].data, [("id".to_owned(), "1".to_owned())]);
+    }
+
+    #[test]
+    fn parse_rejects_unclosed_code() {
+        assert!(matches!(
+            parse("hi [CQ:face,id=1"),
+            Err(CqError::Unclosed { position: 3 })
+        ));
+    }
+}
@@ -1,12 +1,528 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, de, de::DeserializeOwned};
+use serde_json::{Map, Value};
 
-use super::basic_type::Sex;
+use crate::api::{HonorType, SetFriendAddRequest, SetGroupAddRequest, StatusResponse};
 
+use super::{
+    basic_type::{GroupRequestType, GroupRole, Sex},
+    cq,
+    message::JsonMsgRecv,
+};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// 实现不保证提供发送者的各个字段，缺少时为默认值
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Sender {
+    #[serde(default)]
     pub user_id: i64,
+    #[serde(default)]
     pub nickname: String,
+    #[serde(default)]
     pub sex: Sex,
+    #[serde(default)]
     pub age: i32,
+    // 以下仅群消息
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub card: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub area: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<GroupRole>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+/// 各事件枚举只在类型未知时解析为 `Other`，已知的事件解析失败时返回错误
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "post_type", rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
+pub enum Event {
+    Message(MessageEvent),
+    Notice(NoticeEvent),
+    Request(RequestEvent),
+    MetaEvent(MetaEvent),
+    /// 未知的事件，如 `message_sent`，原样保留
+    #[serde(untagged)]
+    Other(Map<String, Value>),
+}
+
+fn field<'a>(map: &'a Map<String, Value>, key: &str) -> Option<&'a str> {
+    map.get(key).and_then(Value::as_str)
+}
+
+fn from_map<T: DeserializeOwned, E: de::Error>(map: Map<String, Value>) -> Result<T, E> {
+    T::deserialize(Value::Object(map)).map_err(E::custom)
+}
+
+impl<'de> Deserialize<'de> for Event {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let map = Map::deserialize(deserializer)?;
+        Ok(match field(&map, "post_type") {
+            Some("message") => Event::Message(from_map(map)?),
+            Some("notice") => Event::Notice(from_map(map)?),
+            Some("request") => Event::Request(from_map(map)?),
+            Some("meta_event") => Event::MetaEvent(from_map(map)?),
+            _ => Event::Other(map),
+        })
+    }
+}
+
+impl Event {
+    pub fn time(&self) -> i64 {
+        match self {
+            Event::Message(MessageEvent::Private(e)) => e.time,
+            Event::Message(MessageEvent::Group(e)) => e.time,
+            Event::Notice(e) => e.time,
+            Event::Request(e) => e.time,
+            Event::MetaEvent(e) => e.time,
+            Event::Message(MessageEvent::Other(e)) | Event::Other(e) => {
+                e.get("time").and_then(Value::as_i64).unwrap_or_default()
+            }
+        }
+    }
+
+    pub fn self_id(&self) -> i64 {
+        match self {
+            Event::Message(MessageEvent::Private(e)) => e.self_id,
+            Event::Message(MessageEvent::Group(e)) => e.self_id,
+            Event::Notice(e) => e.self_id,
+            Event::Request(e) => e.self_id,
+            Event::MetaEvent(e) => e.self_id,
+            Event::Message(MessageEvent::Other(e)) | Event::Other(e) => {
+                e.get("self_id").and_then(Value::as_i64).unwrap_or_default()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "message_type", rename_all = "snake_case")]
+pub enum MessageEvent {
+    Private(PrivateMessageEvent),
+    Group(GroupMessageEvent),
+    /// 未知的 `message_type` 或 `sub_type`
+    #[serde(untagged)]
+    Other(Map<String, Value>),
+}
+
+impl<'de> Deserialize<'de> for MessageEvent {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let map = Map::deserialize(deserializer)?;
+        Ok(
+            match (field(&map, "message_type"), field(&map, "sub_type")) {
+                (Some("private"), Some("friend" | "group" | "other")) => {
+                    MessageEvent::Private(from_map(map)?)
+                }
+                (Some("group"), Some("normal" | "anonymous" | "notice")) => {
+                    MessageEvent::Group(from_map(map)?)
+                }
+                _ => MessageEvent::Other(map),
+            },
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrivateMessageSubType {
+    Friend,
+    Group,
+    Other,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrivateMessageEvent {
+    pub time: i64,
+    pub self_id: i64,
+    pub sub_type: PrivateMessageSubType,
+    pub message_id: i32,
+    pub user_id: i64,
+    #[serde(deserialize_with = "cq::json_or_cq")]
+    pub message: JsonMsgRecv,
+    pub raw_message: String,
+    pub font: i32,
+    #[serde(default)]
+    pub sender: Sender,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupMessageSubType {
+    Normal,
+    Anonymous,
+    Notice,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Anonymous {
+    pub id: i64,
+    pub name: String,
+    pub flag: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupMessageEvent {
+    pub time: i64,
+    pub self_id: i64,
+    pub sub_type: GroupMessageSubType,
+    pub message_id: i32,
+    pub group_id: i64,
+    pub user_id: i64,
+    #[serde(default)]
+    pub anonymous: Option<Anonymous>,
+    #[serde(deserialize_with = "cq::json_or_cq")]
+    pub message: JsonMsgRecv,
+    pub raw_message: String,
+    pub font: i32,
+    #[serde(default)]
+    pub sender: Sender,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoticeEvent {
+    pub time: i64,
+    pub self_id: i64,
+    #[serde(flatten)]
+    pub notice: Notice,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "notice_type", rename_all = "snake_case")]
+pub enum Notice {
+    GroupUpload(GroupUploadNotice),
+    GroupAdmin(GroupAdminNotice),
+    GroupDecrease(GroupDecreaseNotice),
+    GroupIncrease(GroupIncreaseNotice),
+    GroupBan(GroupBanNotice),
+    FriendAdd(FriendAddNotice),
+    GroupRecall(GroupRecallNotice),
+    FriendRecall(FriendRecallNotice),
+    Notify(NotifyNotice),
+    /// 未知的 `notice_type` 或 `sub_type`，包含 `notice_type` 在内的所有字段
+    #[serde(untagged)]
+    Other(Map<String, Value>),
+}
+
+impl<'de> Deserialize<'de> for Notice {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let map = Map::deserialize(deserializer)?;
+        Ok(
+            match (field(&map, "notice_type"), field(&map, "sub_type")) {
+                (Some("group_upload"), _) => Notice::GroupUpload(from_map(map)?),
+                (Some("group_admin"), Some("set" | "unset")) => Notice::GroupAdmin(from_map(map)?),
+                (Some("group_decrease"), Some("leave" | "kick" | "kick_me")) => {
+                    Notice::GroupDecrease(from_map(map)?)
+                }
+                (Some("group_increase"), Some("approve" | "invite")) => {
+                    Notice::GroupIncrease(from_map(map)?)
+                }
+                (Some("group_ban"), Some("ban" | "lift_ban")) => Notice::GroupBan(from_map(map)?),
+                (Some("friend_add"), _) => Notice::FriendAdd(from_map(map)?),
+                (Some("group_recall"), _) => Notice::GroupRecall(from_map(map)?),
+                (Some("friend_recall"), _) => Notice::FriendRecall(from_map(map)?),
+                (Some("notify"), Some("poke" | "lucky_king" | "honor")) => {
+                    Notice::Notify(from_map(map)?)
+                }
+                _ => Notice::Other(map),
+            },
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupFile {
+    pub id: String,
+    pub name: String,
+    /// 单位：字节
+    pub size: i64,
+    pub busid: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupUploadNotice {
+    pub group_id: i64,
+    pub user_id: i64,
+    pub file: GroupFile,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupAdminChange {
+    pub group_id: i64,
+    pub user_id: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "sub_type", rename_all = "snake_case")]
+pub enum GroupAdminNotice {
+    Set(GroupAdminChange),
+    Unset(GroupAdminChange),
+}
+
+impl GroupAdminNotice {
+    pub fn change(&self) -> &GroupAdminChange {
+        match self {
+            GroupAdminNotice::Set(change) | GroupAdminNotice::Unset(change) => change,
+        }
+    }
+
+    /// 变更后的身份
+    pub fn role(&self) -> GroupRole {
+        match self {
+            GroupAdminNotice::Set(_) => GroupRole::Admin,
+            GroupAdminNotice::Unset(_) => GroupRole::Member,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupMemberChange {
+    pub group_id: i64,
+    pub operator_id: i64,
+    pub user_id: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "sub_type", rename_all = "snake_case")]
+pub enum GroupDecreaseNotice {
+    Leave(GroupMemberChange),
+    Kick(GroupMemberChange),
+    /// 登录号被踢
+    KickMe(GroupMemberChange),
+}
+
+impl GroupDecreaseNotice {
+    pub fn change(&self) -> &GroupMemberChange {
+        match self {
+            GroupDecreaseNotice::Leave(change)
+            | GroupDecreaseNotice::Kick(change)
+            | GroupDecreaseNotice::KickMe(change) => change,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "sub_type", rename_all = "snake_case")]
+pub enum GroupIncreaseNotice {
+    Approve(GroupMemberChange),
+    Invite(GroupMemberChange),
+}
+
+impl GroupIncreaseNotice {
+    pub fn change(&self) -> &GroupMemberChange {
+        match self {
+            GroupIncreaseNotice::Approve(change) | GroupIncreaseNotice::Invite(change) => change,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupBanChange {
+    pub group_id: i64,
+    pub operator_id: i64,
+    /// 全员禁言时为 0
+    pub user_id: i64,
+    /// 单位：秒
+    pub duration: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "sub_type", rename_all = "snake_case")]
+pub enum GroupBanNotice {
+    Ban(GroupBanChange),
+    LiftBan(GroupBanChange),
+}
+
+impl GroupBanNotice {
+    pub fn change(&self) -> &GroupBanChange {
+        match self {
+            GroupBanNotice::Ban(change) | GroupBanNotice::LiftBan(change) => change,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FriendAddNotice {
+    pub user_id: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupRecallNotice {
+    pub group_id: i64,
+    pub user_id: i64,
+    pub operator_id: i64,
+    pub message_id: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FriendRecallNotice {
+    pub user_id: i64,
+    pub message_id: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PokeNotify {
+    /// 私聊戳一戳时没有
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_id: Option<i64>,
+    pub user_id: i64,
+    pub target_id: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LuckyKingNotify {
+    pub group_id: i64,
+    /// 红包发送者
+    pub user_id: i64,
+    /// 运气王
+    pub target_id: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HonorNotify {
+    pub group_id: i64,
+    pub honor_type: HonorType,
+    pub user_id: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "sub_type", rename_all = "snake_case")]
+pub enum NotifyNotice {
+    Poke(PokeNotify),
+    LuckyKing(LuckyKingNotify),
+    Honor(HonorNotify),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestEvent {
+    pub time: i64,
+    pub self_id: i64,
+    #[serde(flatten)]
+    pub request: Request,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "request_type", rename_all = "snake_case")]
+pub enum Request {
+    Friend(FriendRequest),
+    Group(GroupRequest),
+    /// 未知的 `request_type` 或 `sub_type`
+    #[serde(untagged)]
+    Other(Map<String, Value>),
+}
+
+impl<'de> Deserialize<'de> for Request {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let map = Map::deserialize(deserializer)?;
+        Ok(
+            match (field(&map, "request_type"), field(&map, "sub_type")) {
+                (Some("friend"), _) => Request::Friend(from_map(map)?),
+                (Some("group"), Some("add" | "invite")) => Request::Group(from_map(map)?),
+                _ => Request::Other(map),
+            },
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FriendRequest {
+    pub user_id: i64,
+    pub comment: String,
+    pub flag: String,
+}
+
+impl FriendRequest {
+    pub fn approve(&self, remark: Option<String>) -> SetFriendAddRequest {
+        SetFriendAddRequest {
+            flag: self.flag.clone(),
+            approve: true,
+            remark,
+        }
+    }
+
+    pub fn reject(&self) -> SetFriendAddRequest {
+        SetFriendAddRequest {
+            flag: self.flag.clone(),
+            approve: false,
+            remark: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupRequest {
+    pub sub_type: GroupRequestType,
+    pub group_id: i64,
+    pub user_id: i64,
+    pub comment: String,
+    pub flag: String,
+}
+
+impl GroupRequest {
+    pub fn approve(&self) -> SetGroupAddRequest {
+        SetGroupAddRequest {
+            flag: self.flag.clone(),
+            sub_type: self.sub_type,
+            approve: true,
+            reason: None,
+        }
+    }
+
+    pub fn reject(&self, reason: Option<String>) -> SetGroupAddRequest {
+        SetGroupAddRequest {
+            flag: self.flag.clone(),
+            sub_type: self.sub_type,
+            approve: false,
+            reason,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetaEvent {
+    pub time: i64,
+    pub self_id: i64,
+    #[serde(flatten)]
+    pub meta: Meta,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "meta_event_type", rename_all = "snake_case")]
+pub enum Meta {
+    Lifecycle(Lifecycle),
+    Heartbeat(Heartbeat),
+    /// 未知的 `meta_event_type` 或 `sub_type`
+    #[serde(untagged)]
+    Other(Map<String, Value>),
+}
+
+impl<'de> Deserialize<'de> for Meta {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let map = Map::deserialize(deserializer)?;
+        Ok(
+            match (field(&map, "meta_event_type"), field(&map, "sub_type")) {
+                (Some("lifecycle"), Some("enable" | "disable" | "connect")) => {
+                    Meta::Lifecycle(from_map(map)?)
+                }
+                (Some("heartbeat"), _) => Meta::Heartbeat(from_map(map)?),
+                _ => Meta::Other(map),
+            },
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "sub_type", rename_all = "snake_case")]
+pub enum Lifecycle {
+    Enable,
+    Disable,
+    /// 仅 WebSocket 连接建立时
+    Connect,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Heartbeat {
+    pub status: StatusResponse,
+    /// 到下次心跳的间隔，单位：毫秒
+    pub interval: i64,
 }
@@ -0,0 +1,147 @@
+use crate::PipeOps;
+
+use super::message::{AtQqType, ImageRecv, JsonMsgRecv, RecordRecv, Recv, Text, VideoRecv};
+
+#[derive(Debug, Clone, Copy)]
+pub enum MediaRef<'a> {
+    Image(&'a ImageRecv),
+    Record(&'a RecordRecv),
+    Video(&'a VideoRecv),
+}
+
+impl<'a> MediaRef<'a> {
+    pub fn file(&self) -> &'a str {
+        match self {
+            MediaRef::Image(image) => &image.file,
+            MediaRef::Record(record) => &record.file,
+            MediaRef::Video(video) => &video.file,
+        }
+    }
+
+    pub fn url(&self) -> Option<&'a str> {
+        match self {
+            MediaRef::Image(image) => image.url.as_deref(),
+            MediaRef::Record(record) => record.url.as_deref(),
+            MediaRef::Video(video) => video.url.as_deref(),
+        }
+    }
+}
+
+fn is_blank(segment: &Recv) -> bool {
+    matches!(segment, Recv::Text(Text { text }) if text.trim().is_empty())
+}
+
+/// 去掉回复之后第一段纯文本开头的空白
+fn trim_start(mut segments: Vec<Recv>) -> Vec<Recv> {
+    let Some(index) = segments
+        .iter()
+        .position(|segment| !matches!(segment, Recv::Reply(_)))
+    else {
+        return segments;
+    };
+    if let Recv::Text(text) = &mut segments[index] {
+        text.text = text.text.trim_start().into();
+        if text.text.is_empty() {
+            segments.remove(index);
+        }
+    }
+    segments
+}
+
+impl JsonMsgRecv {
+    /// 所有纯文本段拼接后的内容
+    pub fn plain_text(&self) -> String {
+        self.segments()
+            .iter()
+            .filter_map(|segment| match segment {
+                Recv::Text(text) => Some(text.text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn mentions(&self) -> impl Iterator<Item = &AtQqType> {
+        self.segments().iter().filter_map(|segment| match segment {
+            Recv::At(at) => Some(&at.qq),
+            _ => None,
+        })
+    }
+
+    pub fn is_mentioned(&self, user_id: i64) -> bool {
+        let user_id = user_id.to_string();
+        self.mentions()
+            .any(|qq| matches!(qq, AtQqType::Single(qq) if *qq == user_id))
+    }
+
+    pub fn mentions_all(&self) -> bool {
+        self.mentions().any(|qq| *qq == AtQqType::All)
+    }
+
+    /// 被回复的消息 ID
+    pub fn reply_to(&self) -> Option<&str> {
+        self.segments().iter().find_map(|segment| match segment {
+            Recv::Reply(reply) => Some(reply.id.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn media(&self) -> Vec<MediaRef<'_>> {
+        self.segments()
+            .iter()
+            .filter_map(|segment| match segment {
+                Recv::Image(image) => Some(MediaRef::Image(image)),
+                Recv::Record(record) => Some(MediaRef::Record(record)),
+                Recv::Video(video) => Some(MediaRef::Video(video)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// 去掉开头的回复与 @`user_id`，以及其后的空白
+    ///
+    /// 开头没有 @`user_id` 时返回 `None`
+    pub fn strip_mention(&self, user_id: i64) -> Option<JsonMsgRecv> {
+        let user_id = user_id.to_string();
+        let segments = self.segments();
+        let start = segments
+            .iter()
+            .position(|segment| !matches!(segment, Recv::Reply(_)) && !is_blank(segment))?;
+        match &segments[start] {
+            Recv::At(at) if matches!(&at.qq, AtQqType::Single(qq) if *qq == user_id) => {}
+            _ => return None,
+        }
+        segments[..start]
+            .iter()
+            .filter(|segment| matches!(segment, Recv::Reply(_)))
+            .chain(&segments[start + 1..])
+            .cloned()
+            .collect::<Vec<_>>()
+            .pipe(trim_start)
+            .pipe(JsonMsgRecv::Array)
+            .pipe(Some)
+    }
+
+    /// 去掉开头纯文本中的 `prefix`，如命令前缀 `/`
+    ///
+    /// 开头的回复会保留，开头不是 `prefix` 时返回 `None`
+    pub fn strip_prefix(&self, prefix: &str) -> Option<JsonMsgRecv> {
+        let segments = self.segments();
+        let start = segments
+            .iter()
+            .position(|segment| !matches!(segment, Recv::Reply(_)) && !is_blank(segment))?;
+        let Recv::Text(text) = &segments[start] else {
+            return None;
+        };
+        let rest = text.text.trim_start().strip_prefix(prefix)?;
+        let mut result = segments[..start]
+            .iter()
+            .filter(|segment| matches!(segment, Recv::Reply(_)))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !rest.is_empty() {
+            result.push(Recv::Text(Text { text: rest.into() }));
+        }
+        result.extend_from_slice(&segments[start + 1..]);
+        Some(JsonMsgRecv::Array(result))
+    }
+}
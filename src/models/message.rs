@@ -1,6 +1,7 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, de, de::DeserializeOwned};
+use serde_json::{Map, Value};
 
-use crate::models::basic_type::OneBotBool;
+use crate::models::basic_type::{OneBotBool, number_or_string};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Text {
@@ -13,6 +14,7 @@ pub struct Face {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ImageType {
     Flash,
 }
@@ -20,64 +22,122 @@ pub enum ImageType {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImageRecv {
     pub file: String,
-    pub r#type: ImageType,
-    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub r#type: Option<ImageType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImageSend {
     pub file: String,
-    pub r#type: ImageType,
-    pub cache: OneBotBool,
-    pub proxy: OneBotBool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub r#type: Option<ImageType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<OneBotBool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<OneBotBool>,
     // 文档没写
     /// 单位：秒
-    pub timeout: isize,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "number_or_string::option::deserialize"
+    )]
+    pub timeout: Option<isize>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RecordRecv {
     pub file: String,
-    pub magic: OneBotBool,
-    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub magic: Option<OneBotBool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RecordSend {
     pub file: String,
-    pub magic: OneBotBool,
-    pub url: String,
-    pub cache: OneBotBool,
-    pub proxy: OneBotBool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub magic: Option<OneBotBool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<OneBotBool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<OneBotBool>,
     // 文档没写
     /// 单位：秒
-    pub timeout: isize,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "number_or_string::option::deserialize"
+    )]
+    pub timeout: Option<isize>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VideoRecv {
     pub file: String,
-    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VideoSend {
     pub file: String,
-    pub cache: OneBotBool,
-    pub proxy: OneBotBool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<OneBotBool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<OneBotBool>,
     // 文档没写
     /// 单位：秒
-    pub timeout: isize,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "number_or_string::option::deserialize"
+    )]
+    pub timeout: Option<isize>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(untagged)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum AtQqType {
-    #[serde(rename = "all")]
     All,
     Single(String),
 }
 
+impl Serialize for AtQqType {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            AtQqType::All => serializer.serialize_str("all"),
+            AtQqType::Single(qq) => serializer.serialize_str(qq),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for AtQqType {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Number(i64),
+            String(String),
+        }
+        Ok(match Raw::deserialize(deserializer)? {
+            Raw::Number(qq) => AtQqType::Single(qq.to_string()),
+            Raw::String(qq) if qq == "all" => AtQqType::All,
+            Raw::String(qq) => AtQqType::Single(qq),
+        })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct At {
     pub qq: AtQqType,
@@ -93,18 +153,24 @@ pub struct Shake {}
 // TODO: Static Type
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PokeReceive {
+    #[serde(deserialize_with = "number_or_string::deserialize")]
     pub r#type: isize,
+    #[serde(deserialize_with = "number_or_string::deserialize")]
     pub id: isize,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PokeRecv {
+    #[serde(deserialize_with = "number_or_string::deserialize")]
     pub r#type: isize,
+    #[serde(deserialize_with = "number_or_string::deserialize")]
     pub id: isize,
-    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnonymousSend {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub ignore: Option<OneBotBool>,
 }
 
@@ -120,11 +186,14 @@ pub struct ShareReceive {
 pub struct ShareRecv {
     pub url: String,
     pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub content: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub image: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum ContactType {
     Group { id: String },
     Qq { id: String },
@@ -142,7 +211,9 @@ pub struct LocationRecv {
 pub struct LocationSend {
     pub lat: String,
     pub lon: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub content: Option<String>,
 }
 
@@ -161,6 +232,10 @@ pub enum Music {
         url: String,
         audio: String,
         title: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        content: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        image: Option<String>,
     },
 }
 
@@ -178,8 +253,45 @@ pub struct Forward {
 pub struct NodeRecv {
     pub id: String,
 }
+/// 合并转发的节点
+///
+/// 自定义节点的 `content` 中可以再嵌套 [`Send::NodeSend`]，构成多层合并转发
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct NodeSend {}
+#[serde(untagged)]
+pub enum NodeSend {
+    /// 引用已有的消息
+    Reference {
+        id: String,
+    },
+    Json(Box<NodeMergeForwardJson>),
+    Cq(NodeMergeForwardCq),
+}
+
+impl NodeSend {
+    pub fn reference(message_id: i32) -> Self {
+        Self::Reference {
+            id: message_id.to_string(),
+        }
+    }
+
+    pub fn custom(
+        user_id: i64,
+        nickname: impl Into<String>,
+        content: impl Into<JsonMsgSend>,
+    ) -> Self {
+        Self::Json(Box::new(NodeMergeForwardJson {
+            user_id: user_id.to_string(),
+            nickname: nickname.into(),
+            content: content.into(),
+        }))
+    }
+}
+
+impl From<NodeSend> for Send {
+    fn from(value: NodeSend) -> Self {
+        Send::NodeSend(value)
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodeMergeForwardCq {
@@ -205,7 +317,8 @@ pub struct Json {
     pub data: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// 只在 `type` 未知时解析为 [`Send::Other`]，已知的段解析失败时返回错误
+#[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "snake_case", tag = "type", content = "data")]
 pub enum Send {
     Text(Text),
@@ -225,12 +338,21 @@ pub enum Send {
     Music(Music),
     Reply(Reply),
     Forward(Forward),
+    #[serde(rename = "node")]
     NodeSend(NodeSend),
     Xml(Xml),
     Json(Json),
+    /// 未知的段，原样保留
+    #[serde(untagged)]
+    Other {
+        r#type: String,
+        #[serde(default, skip_serializing_if = "Value::is_null")]
+        data: Value,
+    },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// 只在 `type` 未知时解析为 [`Recv::Other`]，已知的段解析失败时返回错误
+#[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "snake_case", tag = "type", content = "data")]
 pub enum Recv {
     Text(Text),
@@ -249,27 +371,138 @@ pub enum Recv {
     Music(Music),
     Reply(Reply),
     Forward(Forward),
+    #[serde(rename = "node")]
     NodeSend(NodeRecv),
     Xml(Xml),
     Json(Json),
+    /// 未知的段，如 NapCat 的 `mface`、`markdown`、`file`，原样保留
+    #[serde(untagged)]
+    Other {
+        r#type: String,
+        #[serde(default, skip_serializing_if = "Value::is_null")]
+        data: Value,
+    },
+}
+
+/// `{"type": .., "data": ..}`，先读出类型再决定怎样解析 `data`
+#[derive(Deserialize)]
+struct RawSegment {
+    r#type: String,
+    #[serde(default)]
+    data: Value,
+}
+
+/// 没有 `data` 的段按 `{}` 解析
+fn segment_data<T: DeserializeOwned, E: de::Error>(data: Value) -> Result<T, E> {
+    match data {
+        Value::Null => T::deserialize(Value::Object(Map::new())),
+        data => T::deserialize(data),
+    }
+    .map_err(E::custom)
+}
+
+impl<'de> Deserialize<'de> for Send {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let RawSegment { r#type, data } = RawSegment::deserialize(deserializer)?;
+        Ok(match r#type.as_str() {
+            "text" => Send::Text(segment_data(data)?),
+            "face" => Send::Face(segment_data(data)?),
+            "image" => Send::Image(segment_data(data)?),
+            "record" => Send::Record(segment_data(data)?),
+            "video" => Send::Video(segment_data(data)?),
+            "at" => Send::At(segment_data(data)?),
+            "rps" => Send::Rps(segment_data(data)?),
+            "dice" => Send::Dice(segment_data(data)?),
+            "shake" => Send::Shake(segment_data(data)?),
+            "poke" => Send::Poke(segment_data(data)?),
+            "anonymous" => Send::Anonymous(segment_data(data)?),
+            "share" => Send::Share(segment_data(data)?),
+            "contact" => Send::Contact(segment_data(data)?),
+            "location" => Send::Location(segment_data(data)?),
+            "music" => Send::Music(segment_data(data)?),
+            "reply" => Send::Reply(segment_data(data)?),
+            "forward" => Send::Forward(segment_data(data)?),
+            "node" => Send::NodeSend(segment_data(data)?),
+            "xml" => Send::Xml(segment_data(data)?),
+            "json" => Send::Json(segment_data(data)?),
+            _ => Send::Other { r#type, data },
+        })
+    }
 }
 
-// TODO: Serialize/Deserialize Cq
+impl<'de> Deserialize<'de> for Recv {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let RawSegment { r#type, data } = RawSegment::deserialize(deserializer)?;
+        Ok(match r#type.as_str() {
+            "text" => Recv::Text(segment_data(data)?),
+            "face" => Recv::Face(segment_data(data)?),
+            "image" => Recv::Image(segment_data(data)?),
+            "record" => Recv::Record(segment_data(data)?),
+            "video" => Recv::Video(segment_data(data)?),
+            "at" => Recv::At(segment_data(data)?),
+            "rps" => Recv::Rps(segment_data(data)?),
+            "dice" => Recv::Dice(segment_data(data)?),
+            "shake" => Recv::Shake(segment_data(data)?),
+            "poke" => Recv::Poke(segment_data(data)?),
+            "share" => Recv::Share(segment_data(data)?),
+            "contact" => Recv::Contact(segment_data(data)?),
+            "location" => Recv::Location(segment_data(data)?),
+            "music" => Recv::Music(segment_data(data)?),
+            "reply" => Recv::Reply(segment_data(data)?),
+            "forward" => Recv::Forward(segment_data(data)?),
+            "node" => Recv::NodeSend(segment_data(data)?),
+            "xml" => Recv::Xml(segment_data(data)?),
+            "json" => Recv::Json(segment_data(data)?),
+            _ => Recv::Other { r#type, data },
+        })
+    }
+}
+
+/// CQ 码字符串，解析与生成见 [`super::cq`]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CqMsg(pub String);
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize)]
 #[serde(untagged)]
 pub enum JsonMsgSend {
     Segment(Send),
     Array(Vec<Send>),
 }
 
-// TODO: JsonMsgSend into CqMsg
+/// 按是否为数组选择变体，保留段的解析错误
+impl<'de> Deserialize<'de> for JsonMsgSend {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Value::deserialize(deserializer)? {
+            segments @ Value::Array(_) => Vec::deserialize(segments).map(JsonMsgSend::Array),
+            segment => Send::deserialize(segment).map(JsonMsgSend::Segment),
+        }
+        .map_err(de::Error::custom)
+    }
+}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize)]
 #[serde(untagged)]
 pub enum JsonMsgRecv {
     Segment(Recv),
     Array(Vec<Recv>),
 }
+
+/// 按是否为数组选择变体，保留段的解析错误
+impl<'de> Deserialize<'de> for JsonMsgRecv {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Value::deserialize(deserializer)? {
+            segments @ Value::Array(_) => Vec::deserialize(segments).map(JsonMsgRecv::Array),
+            segment => Recv::deserialize(segment).map(JsonMsgRecv::Segment),
+        }
+        .map_err(de::Error::custom)
+    }
+}
+
+impl JsonMsgRecv {
+    pub fn segments(&self) -> &[Recv] {
+        match self {
+            JsonMsgRecv::Segment(segment) => std::slice::from_ref(segment),
+            JsonMsgRecv::Array(segments) => segments,
+        }
+    }
+}
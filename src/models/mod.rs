@@ -1,3 +1,8 @@
 pub mod basic_type;
+pub mod builder;
+pub mod cq;
 pub mod event;
+pub mod inspect;
 pub mod message;
+pub mod quick_operation;
+pub mod resend;
@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+
+use super::message::{CqMsg, JsonMsgSend};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum QuickReply {
+    Json(JsonMsgSend),
+    Cq(CqMsg),
+}
+
+impl From<JsonMsgSend> for QuickReply {
+    fn from(value: JsonMsgSend) -> Self {
+        Self::Json(value)
+    }
+}
+
+impl From<CqMsg> for QuickReply {
+    fn from(value: CqMsg) -> Self {
+        Self::Cq(value)
+    }
+}
+
+/// 私聊消息只会用到 `reply` 与 `auto_escape`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MessageQuickOperation {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply: Option<QuickReply>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_escape: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub at_sender: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delete: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kick: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ban: Option<bool>,
+    /// 单位：秒
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ban_duration: Option<i32>,
+}
+
+impl MessageQuickOperation {
+    pub fn reply(reply: impl Into<QuickReply>) -> Self {
+        Self {
+            reply: Some(reply.into()),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FriendRequestQuickOperation {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub approve: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remark: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GroupRequestQuickOperation {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub approve: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum QuickOperation {
+    Message(MessageQuickOperation),
+    FriendRequest(FriendRequestQuickOperation),
+    GroupRequest(GroupRequestQuickOperation),
+}
+
+impl From<MessageQuickOperation> for QuickOperation {
+    fn from(value: MessageQuickOperation) -> Self {
+        Self::Message(value)
+    }
+}
+
+impl From<FriendRequestQuickOperation> for QuickOperation {
+    fn from(value: FriendRequestQuickOperation) -> Self {
+        Self::FriendRequest(value)
+    }
+}
+
+impl From<GroupRequestQuickOperation> for QuickOperation {
+    fn from(value: GroupRequestQuickOperation) -> Self {
+        Self::GroupRequest(value)
+    }
+}
@@ -0,0 +1,156 @@
+use std::{
+    error::Error,
+    fmt::{self, Display},
+};
+
+use super::message::{
+    ImageRecv, ImageSend, JsonMsgRecv, JsonMsgSend, LocationRecv, LocationSend, RecordRecv,
+    RecordSend, Recv, Send, VideoRecv, VideoSend,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResendError {
+    /// 所有段都无法重新发送
+    Empty,
+}
+
+impl Display for ResendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResendError::Empty => f.write_str("no segment can be resent"),
+        }
+    }
+}
+
+impl Error for ResendError {}
+
+/// 有 `http(s)` 链接时按链接重发，否则按 `file` 重发
+///
+/// `file` 通常只在收到它的实现上有效，转发到其他账号时链接更可靠
+fn resend_file(file: &str, url: Option<&str>) -> String {
+    match url {
+        Some(url) if url.starts_with("http://") || url.starts_with("https://") => url.into(),
+        _ => file.into(),
+    }
+}
+
+impl From<&ImageRecv> for ImageSend {
+    fn from(value: &ImageRecv) -> Self {
+        Self {
+            file: resend_file(&value.file, value.url.as_deref()),
+            r#type: value.r#type.clone(),
+            cache: None,
+            proxy: None,
+            timeout: None,
+        }
+    }
+}
+
+impl From<&RecordRecv> for RecordSend {
+    fn from(value: &RecordRecv) -> Self {
+        Self {
+            file: resend_file(&value.file, value.url.as_deref()),
+            magic: value.magic,
+            url: None,
+            cache: None,
+            proxy: None,
+            timeout: None,
+        }
+    }
+}
+
+impl From<&VideoRecv> for VideoSend {
+    fn from(value: &VideoRecv) -> Self {
+        Self {
+            file: resend_file(&value.file, value.url.as_deref()),
+            cache: None,
+            proxy: None,
+            timeout: None,
+        }
+    }
+}
+
+impl From<&LocationRecv> for LocationSend {
+    fn from(value: &LocationRecv) -> Self {
+        Self {
+            lat: value.lat.clone(),
+            lon: value.lon.clone(),
+            title: Some(value.title.clone()),
+            content: Some(value.content.clone()),
+        }
+    }
+}
+
+impl Recv {
+    /// 转为可以发送的段，无法发送的段返回 `None`
+    pub fn to_send(&self) -> Option<Send> {
+        Some(match self {
+            Recv::Text(text) => Send::Text(text.clone()),
+            Recv::Face(face) => Send::Face(face.clone()),
+            Recv::Image(image) => Send::Image(image.into()),
+            Recv::Record(record) => Send::Record(record.into()),
+            Recv::Video(video) => Send::Video(video.into()),
+            Recv::At(at) => Send::At(at.clone()),
+            Recv::Rps(rps) => Send::Rps(rps.clone()),
+            Recv::Dice(dice) => Send::Dice(dice.clone()),
+            Recv::Shake(shake) => Send::Shake(shake.clone()),
+            Recv::Poke(poke) => Send::Poke(poke.clone()),
+            Recv::Share(share) => Send::Share(share.clone()),
+            Recv::Contact(contact) => Send::Contact(contact.clone()),
+            Recv::Location(location) => Send::Location(location.into()),
+            Recv::Music(music) => Send::Music(music.clone()),
+            Recv::Reply(reply) => Send::Reply(reply.clone()),
+            // 合并转发只能接收，`id` 也不是消息 ID，无法作为节点引用
+            Recv::Forward(_) => return None,
+            // 合并转发的节点只能出现在合并转发中
+            Recv::NodeSend(_) => return None,
+            Recv::Xml(xml) => Send::Xml(xml.clone()),
+            Recv::Json(json) => Send::Json(json.clone()),
+            Recv::Other { r#type, data } => Send::Other {
+                r#type: r#type.clone(),
+                data: data.clone(),
+            },
+        })
+    }
+}
+
+fn resend(value: &JsonMsgRecv, reply: bool) -> Result<JsonMsgSend, ResendError> {
+    let segments = value
+        .segments()
+        .iter()
+        .filter(|segment| reply || !matches!(segment, Recv::Reply(_)))
+        .filter_map(Recv::to_send)
+        .collect::<Vec<_>>();
+    if segments.is_empty() {
+        return Err(ResendError::Empty);
+    }
+    Ok(JsonMsgSend::Array(segments))
+}
+
+impl JsonMsgRecv {
+    /// 发送到其他会话时使用，额外丢弃 [`Recv::Reply`]
+    ///
+    /// 回复引用的消息只在原会话中有效
+    pub fn resend_elsewhere(&self) -> Result<JsonMsgSend, ResendError> {
+        resend(self, false)
+    }
+}
+
+/// 丢弃无法发送的段，全部丢弃时返回 [`ResendError::Empty`]
+///
+/// 保留 [`Recv::Reply`]，适合在原会话中重发
+impl TryFrom<&JsonMsgRecv> for JsonMsgSend {
+    type Error = ResendError;
+
+    fn try_from(value: &JsonMsgRecv) -> Result<Self, Self::Error> {
+        resend(value, true)
+    }
+}
+
+impl TryFrom<JsonMsgRecv> for JsonMsgSend {
+    type Error = ResendError;
+
+    fn try_from(value: JsonMsgRecv) -> Result<Self, Self::Error> {
+        JsonMsgSend::try_from(&value)
+    }
+}